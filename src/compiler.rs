@@ -0,0 +1,768 @@
+use crate::chunk::{Chunk, Constant, FunctionProto, OpCode};
+use crate::parser::{
+    BinaryOperator, Expr, FunctionDecl, LiteralValue, LogicalOperator, Statement, UnaryOperator,
+};
+use crate::scanner::Token;
+use crate::symbol::Symbol;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    // None for a slot that can't be named, such as the function being called
+    name: Option<Symbol>,
+    depth: usize,
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    // whether the upvalue captures a local of the enclosing function or one of its upvalues
+    is_local: bool,
+}
+
+struct FunctionState {
+    function_type: FunctionType,
+    name: String,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(function_type: FunctionType, name: String, arity: usize) -> Self {
+        // slot zero holds the function being called, or the receiver for methods
+        let slot_zero = match function_type {
+            FunctionType::Method | FunctionType::Initializer => Some(Symbol::THIS),
+            FunctionType::Script | FunctionType::Function => None,
+        };
+        FunctionState {
+            function_type,
+            name,
+            arity,
+            chunk: Chunk::default(),
+            locals: vec![Local {
+                name: slot_zero,
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: vec![],
+            scope_depth: 0,
+        }
+    }
+}
+
+// Compiles a resolved program into the function run by the virtual machine. Static errors such
+// as returning from the top level are reported by the resolver before compilation.
+struct Compiler {
+    functions: Vec<FunctionState>,
+    // position of the token most recently compiled, recorded for each emitted byte
+    line: usize,
+    column: usize,
+    length: usize,
+}
+
+pub fn compile(statements: &[Statement]) -> Result<Rc<FunctionProto>, CompileError> {
+    let mut compiler = Compiler {
+        functions: vec![FunctionState::new(FunctionType::Script, String::new(), 0)],
+        line: 1,
+        column: 1,
+        length: 1,
+    };
+
+    for statement in statements {
+        compiler.statement(statement)?;
+    }
+    Ok(Rc::new(compiler.end_function().0))
+}
+
+impl Compiler {
+    fn statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expression(expr) => {
+                self.expression(expr)?;
+                self.emit_op(OpCode::Pop);
+            }
+            Statement::Print(keyword, expr) => {
+                self.expression(expr)?;
+                self.set_position(keyword);
+                self.emit_op(OpCode::Print);
+            }
+            Statement::Declaration(name, initialiser) => {
+                self.set_position(name);
+                match initialiser {
+                    Some(expr) => self.expression(expr)?,
+                    None => self.emit_op(OpCode::Nil),
+                }
+                self.define_variable(name)?;
+            }
+            Statement::Block(statements) => {
+                self.begin_scope();
+                for statement in statements {
+                    self.statement(statement)?;
+                }
+                self.end_scope();
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.statement(then_branch)?;
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump)?;
+                self.emit_op(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump)?;
+            }
+            Statement::While { condition, body } => {
+                let loop_start = self.chunk().code.len();
+                self.expression(condition)?;
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.statement(body)?;
+                self.emit_loop(loop_start)?;
+                self.patch_jump(exit_jump)?;
+                self.emit_op(OpCode::Pop);
+            }
+            Statement::Function(declaration) => {
+                let FunctionDecl {
+                    name,
+                    params,
+                    block,
+                } = declaration.as_ref();
+                self.set_position(name);
+                // locals are usable before the body is compiled, so functions can recurse
+                if self.current().scope_depth > 0 {
+                    self.add_local(name.symbol())?;
+                }
+                self.function(name, params, block, FunctionType::Function)?;
+                if self.current().scope_depth == 0 {
+                    self.define_global(name)?;
+                }
+            }
+            Statement::Return(keyword, value) => {
+                self.set_position(keyword);
+                match value {
+                    Some(expr) => self.expression(expr)?,
+                    None => self.emit_implicit_return_value(),
+                }
+                self.emit_op(OpCode::Return);
+            }
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => self.class(name, superclass, methods)?,
+        }
+        Ok(())
+    }
+
+    fn class(
+        &mut self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Statement],
+    ) -> Result<(), CompileError> {
+        self.set_position(name);
+        let class_name = name.symbol();
+        let name_constant = self.make_constant(Constant::Name(class_name))?;
+        self.emit_op(OpCode::Class);
+        self.emit_u16(name_constant);
+        self.define_variable(name)?;
+
+        if let Some(superclass) = superclass {
+            self.expression(superclass)?;
+            // methods capture the superclass through a local named super
+            self.begin_scope();
+            self.add_local(Symbol::SUPER)?;
+            self.named_variable(class_name)?;
+            self.emit_op(OpCode::Inherit);
+        }
+
+        self.named_variable(class_name)?;
+        for method in methods {
+            if let Statement::Function(declaration) = method {
+                let FunctionDecl {
+                    name,
+                    params,
+                    block,
+                } = declaration.as_ref();
+                self.set_position(name);
+                let function_type = if name.symbol() == Symbol::INIT {
+                    FunctionType::Initializer
+                } else {
+                    FunctionType::Method
+                };
+                self.function(name, params, block, function_type)?;
+                let method_constant = self.make_constant(Constant::Name(name.symbol()))?;
+                self.emit_op(OpCode::Method);
+                self.emit_u16(method_constant);
+            }
+        }
+        self.emit_op(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+        Ok(())
+    }
+
+    fn function(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Statement],
+        function_type: FunctionType,
+    ) -> Result<(), CompileError> {
+        self.functions.push(FunctionState::new(
+            function_type,
+            name.symbol().to_string(),
+            params.len(),
+        ));
+        self.begin_scope();
+        for param in params {
+            self.add_local(param.symbol())?;
+        }
+        for statement in body {
+            self.statement(statement)?;
+        }
+        let (function, upvalues) = self.end_function();
+
+        // the closure belongs to the declaration's line rather than the end of the body
+        self.set_position(name);
+        let constant = self.make_constant(Constant::Function(Rc::new(function)))?;
+        self.emit_op(OpCode::Closure);
+        self.emit_u16(constant);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+
+    fn end_function(&mut self) -> (FunctionProto, Vec<UpvalueRef>) {
+        self.emit_implicit_return_value();
+        self.emit_op(OpCode::Return);
+        let state = self.functions.pop().unwrap();
+        let function = FunctionProto {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: state.upvalues.len(),
+            chunk: state.chunk,
+        };
+        (function, state.upvalues)
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr {
+            Expr::Binary(left, right, op, operator) => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.set_position(operator);
+                match op {
+                    BinaryOperator::Minus => self.emit_op(OpCode::Subtract),
+                    BinaryOperator::Plus => self.emit_op(OpCode::Add),
+                    BinaryOperator::Slash => self.emit_op(OpCode::Divide),
+                    BinaryOperator::Star => self.emit_op(OpCode::Multiply),
+                    BinaryOperator::BangEqual => {
+                        self.emit_op(OpCode::Equal);
+                        self.emit_op(OpCode::Not);
+                    }
+                    BinaryOperator::EqualEqual => self.emit_op(OpCode::Equal),
+                    BinaryOperator::Greater => self.emit_op(OpCode::Greater),
+                    BinaryOperator::GreaterEqual => self.emit_op(OpCode::GreaterEqual),
+                    BinaryOperator::Less => self.emit_op(OpCode::Less),
+                    BinaryOperator::LessEqual => self.emit_op(OpCode::LessEqual),
+                }
+            }
+            Expr::Logical(left, right, op) => {
+                self.expression(left)?;
+                match op {
+                    LogicalOperator::And => {
+                        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+                        self.emit_op(OpCode::Pop);
+                        self.expression(right)?;
+                        self.patch_jump(end_jump)?;
+                    }
+                    LogicalOperator::Or => {
+                        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                        let end_jump = self.emit_jump(OpCode::Jump);
+                        self.patch_jump(else_jump)?;
+                        self.emit_op(OpCode::Pop);
+                        self.expression(right)?;
+                        self.patch_jump(end_jump)?;
+                    }
+                }
+            }
+            Expr::Unary(expr, op, operator) => {
+                self.expression(expr)?;
+                self.set_position(operator);
+                match op {
+                    UnaryOperator::Bang => self.emit_op(OpCode::Not),
+                    UnaryOperator::Minus => self.emit_op(OpCode::Negate),
+                }
+            }
+            Expr::Literal(literal, token) => {
+                self.set_position(token);
+                match literal {
+                    LiteralValue::Number(num) => self.emit_constant(Constant::Number(*num))?,
+                    LiteralValue::String(str) => {
+                        self.emit_constant(Constant::String(str.clone()))?
+                    }
+                    LiteralValue::Boolean(true) => self.emit_op(OpCode::True),
+                    LiteralValue::Boolean(false) => self.emit_op(OpCode::False),
+                    LiteralValue::Nil => self.emit_op(OpCode::Nil),
+                }
+            }
+            Expr::Variable(name, _) => {
+                self.set_position(name);
+                self.named_variable(name.symbol())?;
+            }
+            Expr::Assignment(name, value, _) => {
+                self.expression(value)?;
+                self.set_position(name);
+                let name = name.symbol();
+                if let Some(slot) = self.resolve_local(self.functions.len() - 1, name) {
+                    self.emit_op(OpCode::SetLocal);
+                    self.emit_byte(slot);
+                } else if let Some(index) = self.resolve_upvalue(self.functions.len() - 1, name)? {
+                    self.emit_op(OpCode::SetUpvalue);
+                    self.emit_byte(index);
+                } else {
+                    let constant = self.make_constant(Constant::Name(name))?;
+                    self.emit_op(OpCode::SetGlobal);
+                    self.emit_u16(constant);
+                }
+            }
+            Expr::Call(callee, args, paren) => {
+                self.expression(callee)?;
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                self.set_position(paren);
+                if args.len() > u8::MAX as usize {
+                    return Err(self.error("Can't have more than 255 arguments"));
+                }
+                self.emit_op(OpCode::Call);
+                self.emit_byte(args.len() as u8);
+            }
+            Expr::Get(object, name) => {
+                self.expression(object)?;
+                self.set_position(name);
+                let constant = self.make_constant(Constant::Name(name.symbol()))?;
+                self.emit_op(OpCode::GetProperty);
+                self.emit_u16(constant);
+            }
+            Expr::Set(object, name, value) => {
+                self.expression(object)?;
+                self.expression(value)?;
+                self.set_position(name);
+                let constant = self.make_constant(Constant::Name(name.symbol()))?;
+                self.emit_op(OpCode::SetProperty);
+                self.emit_u16(constant);
+            }
+            Expr::This(keyword, _) => {
+                self.set_position(keyword);
+                self.named_variable(Symbol::THIS)?;
+            }
+            Expr::Super(keyword, method, _) => {
+                self.set_position(keyword);
+                self.named_variable(Symbol::THIS)?;
+                self.named_variable(Symbol::SUPER)?;
+                self.set_position(method);
+                let constant = self.make_constant(Constant::Name(method.symbol()))?;
+                self.emit_op(OpCode::GetSuper);
+                self.emit_u16(constant);
+            }
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    self.expression(part)?;
+                }
+                if parts.len() > u8::MAX as usize {
+                    return Err(self.error("Too many interpolated expressions in one string"));
+                }
+                self.emit_op(OpCode::Interpolate);
+                self.emit_byte(parts.len() as u8);
+            }
+        }
+        Ok(())
+    }
+
+    fn named_variable(&mut self, name: Symbol) -> Result<(), CompileError> {
+        if let Some(slot) = self.resolve_local(self.functions.len() - 1, name) {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(slot);
+        } else if let Some(index) = self.resolve_upvalue(self.functions.len() - 1, name)? {
+            self.emit_op(OpCode::GetUpvalue);
+            self.emit_byte(index);
+        } else {
+            let constant = self.make_constant(Constant::Name(name))?;
+            self.emit_op(OpCode::GetGlobal);
+            self.emit_u16(constant);
+        }
+        Ok(())
+    }
+
+    fn resolve_local(&self, function: usize, name: Symbol) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == Some(name))
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(
+        &mut self,
+        function: usize,
+        name: Symbol,
+    ) -> Result<Option<u8>, CompileError> {
+        if function == 0 {
+            return Ok(None);
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name) {
+            self.functions[function - 1].locals[slot as usize].is_captured = true;
+            return self.add_upvalue(function, slot, true).map(Some);
+        }
+        match self.resolve_upvalue(function - 1, name)? {
+            Some(index) => self.add_upvalue(function, index, false).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(
+        &mut self,
+        function: usize,
+        index: u8,
+        is_local: bool,
+    ) -> Result<u8, CompileError> {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &mut self.functions[function].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            return Err(self.error("Too many closure variables in function"));
+        }
+        upvalues.push(upvalue);
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    fn define_variable(&mut self, name: &Token) -> Result<(), CompileError> {
+        if self.current().scope_depth > 0 {
+            self.add_local(name.symbol())
+        } else {
+            self.define_global(name)
+        }
+    }
+
+    fn define_global(&mut self, name: &Token) -> Result<(), CompileError> {
+        let constant = self.make_constant(Constant::Name(name.symbol()))?;
+        self.emit_op(OpCode::DefineGlobal);
+        self.emit_u16(constant);
+        Ok(())
+    }
+
+    // the value on top of the stack becomes the new local's slot
+    fn add_local(&mut self, name: Symbol) -> Result<(), CompileError> {
+        if self.current().locals.len() == MAX_LOCALS {
+            return Err(self.error("Too many local variables in function"));
+        }
+        let depth = self.current().scope_depth;
+        self.current_mut().locals.push(Local {
+            name: Some(name),
+            depth,
+            is_captured: false,
+        });
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.current_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.current_mut().scope_depth -= 1;
+        let depth = self.current().scope_depth;
+        while let Some(local) = self.current().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            if local.is_captured {
+                self.emit_op(OpCode::CloseUpvalue);
+            } else {
+                self.emit_op(OpCode::Pop);
+            }
+            self.current_mut().locals.pop();
+        }
+    }
+
+    fn emit_implicit_return_value(&mut self) {
+        if self.current().function_type == FunctionType::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+    }
+
+    fn emit_constant(&mut self, constant: Constant) -> Result<(), CompileError> {
+        let index = self.make_constant(constant)?;
+        self.emit_op(OpCode::Constant);
+        self.emit_u16(index);
+        Ok(())
+    }
+
+    fn make_constant(&mut self, constant: Constant) -> Result<u16, CompileError> {
+        let index = self.chunk().add_constant(constant);
+        u16::try_from(index).map_err(|_| self.error("Too many constants in one chunk"))
+    }
+
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_u16(u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    // jumps are relative to the end of the jump instruction
+    fn patch_jump(&mut self, offset: usize) -> Result<(), CompileError> {
+        let jump = self.chunk().code.len() - offset - 2;
+        let jump = u16::try_from(jump).map_err(|_| self.error("Too much code to jump over"))?;
+        let [high, low] = jump.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), CompileError> {
+        self.emit_op(OpCode::Loop);
+        let offset = self.chunk().code.len() - loop_start + 2;
+        let offset = u16::try_from(offset).map_err(|_| self.error("Loop body too large"))?;
+        self.emit_u16(offset);
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        self.emit_byte(high);
+        self.emit_byte(low);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let (line, column, length) = (self.line, self.column, self.length);
+        self.chunk().write(byte, line, column, length);
+    }
+
+    fn set_position(&mut self, token: &Token) {
+        self.line = token.line;
+        self.column = token.column;
+        self.length = token.length();
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current_mut().chunk
+    }
+
+    fn current(&self) -> &FunctionState {
+        self.functions.last().unwrap()
+    }
+
+    fn current_mut(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn error(&self, message: &str) -> CompileError {
+        CompileError {
+            message: String::from(message),
+            line: self.line,
+            column: self.column,
+            length: self.length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::scanner;
+
+    fn compile_source(input: &str) -> Rc<FunctionProto> {
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        compile(&statements).unwrap()
+    }
+
+    fn opcodes(chunk: &Chunk) -> Vec<OpCode> {
+        let mut offset = 0;
+        let mut opcodes = vec![];
+        while offset < chunk.code.len() {
+            let opcode = OpCode::from_byte(chunk.code[offset]).unwrap();
+            offset += 1 + match opcode {
+                OpCode::Constant
+                | OpCode::GetGlobal
+                | OpCode::DefineGlobal
+                | OpCode::SetGlobal
+                | OpCode::GetProperty
+                | OpCode::SetProperty
+                | OpCode::GetSuper
+                | OpCode::Jump
+                | OpCode::JumpIfFalse
+                | OpCode::Loop
+                | OpCode::Class
+                | OpCode::Method => 2,
+                OpCode::GetLocal
+                | OpCode::SetLocal
+                | OpCode::GetUpvalue
+                | OpCode::SetUpvalue
+                | OpCode::Call
+                | OpCode::Interpolate => 1,
+                OpCode::Closure => {
+                    let constant = chunk.read_u16(offset + 1);
+                    match &chunk.constants[constant as usize] {
+                        Constant::Function(function) => 2 + 2 * function.upvalue_count,
+                        _ => panic!("Expected function constant"),
+                    }
+                }
+                _ => 0,
+            };
+            opcodes.push(opcode);
+        }
+        opcodes
+    }
+
+    #[test]
+    fn test_global_declaration() {
+        let function = compile_source("var a = 1 + 2; print a;");
+        assert_eq!(
+            opcodes(&function.chunk),
+            vec![
+                OpCode::Constant,
+                OpCode::Constant,
+                OpCode::Add,
+                OpCode::DefineGlobal,
+                OpCode::GetGlobal,
+                OpCode::Print,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+        assert_eq!(
+            function.chunk.constants,
+            vec![
+                Constant::Number(1.0),
+                Constant::Number(2.0),
+                Constant::Name(Symbol::intern("a"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locals_use_stack_slots() {
+        let function = compile_source("{ var a = 1; var b = a; }");
+        assert_eq!(
+            opcodes(&function.chunk),
+            vec![
+                OpCode::Constant,
+                OpCode::GetLocal,
+                OpCode::Pop,
+                OpCode::Pop,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+        // slot 0 is reserved for the script itself
+        assert_eq!(function.chunk.code[4], 1);
+    }
+
+    #[test]
+    fn test_captured_locals_become_upvalues() {
+        let function = compile_source(
+            "
+            fun outer() {
+                var a = 1;
+                fun inner() {
+                    return a;
+                }
+                return inner;
+            }",
+        );
+        let outer = match &function.chunk.constants[0] {
+            Constant::Function(outer) => outer.clone(),
+            _ => panic!("Expected function constant"),
+        };
+        let inner = match &outer.chunk.constants[1] {
+            Constant::Function(inner) => inner.clone(),
+            _ => panic!("Expected function constant"),
+        };
+        assert_eq!(outer.upvalue_count, 0);
+        assert_eq!(inner.upvalue_count, 1);
+        assert_eq!(
+            opcodes(&inner.chunk),
+            vec![
+                OpCode::GetUpvalue,
+                OpCode::Return,
+                OpCode::Nil,
+                OpCode::Return
+            ]
+        );
+    }
+
+    #[test]
+    fn test_while_loop_jumps() {
+        let function = compile_source("while (true) print 1;");
+        assert_eq!(
+            opcodes(&function.chunk),
+            vec![
+                OpCode::True,
+                OpCode::JumpIfFalse,
+                OpCode::Pop,
+                OpCode::Constant,
+                OpCode::Print,
+                OpCode::Loop,
+                OpCode::Pop,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+        // the loop jumps back to the condition
+        assert_eq!(function.chunk.read_u16(10), 12);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::compiler::CompileError;
+use crate::interpreter::RuntimeError;
+use crate::parser::ParseError;
+use crate::resolver::ResolveError;
+use crate::scanner::{ScanError, Token};
+
+// An error message about a span of the source. Lines and columns count from 1.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    // printed below the source snippet, such as the frames of a runtime error
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn at_token(message: String, token: &Token) -> Self {
+        Diagnostic {
+            message,
+            line: token.line,
+            column: token.column,
+            length: token.length(),
+            notes: vec![],
+        }
+    }
+
+    // renders the message, the offending line of source and a caret underline of the span
+    pub fn render(&self, source: &str) -> String {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let source_line = source
+            .lines()
+            .nth(self.line.saturating_sub(1))
+            .unwrap_or("");
+
+        // keep tabs in the indent so the carets line up with the source above them
+        let indent: String = source_line
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let remaining = source_line
+            .chars()
+            .count()
+            .saturating_sub(indent.chars().count());
+        let carets = "^".repeat(self.length.min(remaining).max(1));
+
+        let mut output = format!(
+            "error: {}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}{}",
+            self.message,
+            gutter,
+            self.line,
+            self.column,
+            gutter,
+            line_number,
+            source_line,
+            gutter,
+            indent,
+            carets
+        );
+        for note in &self.notes {
+            output.push_str(&format!("\n{} = {}", gutter, note));
+        }
+        output
+    }
+}
+
+impl From<&ScanError> for Diagnostic {
+    fn from(error: &ScanError) -> Self {
+        Diagnostic {
+            message: error.error_type.to_string(),
+            line: error.line,
+            column: error.column,
+            length: 1,
+            notes: vec![],
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Diagnostic::at_token(error.to_string(), &error.token)
+    }
+}
+
+impl From<&ResolveError> for Diagnostic {
+    fn from(error: &ResolveError) -> Self {
+        Diagnostic::at_token(error.error_type.to_string(), &error.token)
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        Diagnostic {
+            message: error.error_type.to_string(),
+            line: error.line,
+            column: error.column,
+            length: error.length,
+            notes: error.traceback(),
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic {
+            message: error.message.clone(),
+            line: error.line,
+            column: error.column,
+            length: error.length,
+            notes: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::interpreter::Interpreter;
+    use crate::parser::parse;
+    use crate::resolver::resolve;
+    use crate::scanner;
+    use crate::vm::Vm;
+
+    #[test]
+    fn test_render_underlines_token() {
+        let source = "var a = 1;\nprint a a;";
+        let tokens = scanner::scan(String::from(source)).unwrap();
+        let errors = parse(tokens).unwrap_err();
+
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render(source),
+            "error: expected ';' after value, found 'a'
+ --> line 2, column 9
+  |
+2 | print a a;
+  |         ^"
+        );
+    }
+
+    #[test]
+    fn test_render_scan_error() {
+        let source = "var a = \"abc\\q\";";
+        let errors = scanner::scan(String::from(source)).unwrap_err();
+
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render(source),
+            "error: invalid escape sequence
+ --> line 1, column 13
+  |
+1 | var a = \"abc\\q\";
+  |             ^"
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_tabs_and_multi_character_spans() {
+        let diagnostic = Diagnostic {
+            message: String::from("something went wrong"),
+            line: 10,
+            column: 3,
+            length: 5,
+            notes: vec![String::from("a note")],
+        };
+        let source = "\n".repeat(9) + "\t\tshape.sides;";
+
+        assert_eq!(
+            diagnostic.render(&source),
+            "error: something went wrong
+  --> line 10, column 3
+   |
+10 | \t\tshape.sides;
+   | \t\t^^^^^
+   = a note"
+        );
+    }
+
+    #[test]
+    fn test_render_runtime_error_underlines_token() {
+        let source = "class Shape {}\nvar shape = Shape();\nprint shape.sides;";
+        let tokens = scanner::scan(String::from(source)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let expected = "error: Undefined property 'sides'
+ --> line 3, column 13
+  |
+3 | print shape.sides;
+  |             ^^^^^
+  = [line 3 column 13] in script";
+
+        let error = Interpreter::new().evaluate(&statements).unwrap_err();
+        assert_eq!(Diagnostic::from(&error).render(source), expected);
+        let function = compile(&statements).unwrap();
+        let error = Vm::new().interpret(function).unwrap_err();
+        assert_eq!(Diagnostic::from(&error).render(source), expected);
+    }
+
+    #[test]
+    fn test_render_compile_error() {
+        let mut source = String::from("{\n");
+        for i in 0..256 {
+            source.push_str(&format!("var local{};\n", i));
+        }
+        source.push('}');
+        let tokens = scanner::scan(source.clone()).unwrap();
+        let statements = parse(tokens).unwrap();
+        let error = compile(&statements).unwrap_err();
+
+        assert_eq!(
+            Diagnostic::from(&error).render(&source),
+            "error: Too many local variables in function
+   --> line 257, column 5
+    |
+257 | var local255;
+    |     ^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_render_counts_characters_not_bytes() {
+        let source = "print \"héllo\" café;";
+        let tokens = scanner::scan(String::from(source)).unwrap();
+        let errors = parse(tokens).unwrap_err();
+
+        assert_eq!(
+            Diagnostic::from(&errors[0]).render(source),
+            "error: expected ';' after value, found 'café'
+ --> line 1, column 15
+  |
+1 | print \"héllo\" café;
+  |               ^^^^"
+        );
+    }
+}
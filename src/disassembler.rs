@@ -0,0 +1,227 @@
+use crate::chunk::{Chunk, Constant, FunctionProto, OpCode};
+use std::fmt::Write;
+
+// Renders a compiled function, followed by every function nested in its constant pool, as one
+// instruction per line with its offset, source line and decoded operands
+pub fn disassemble(function: &FunctionProto) -> String {
+    let mut output = String::new();
+    disassemble_function(function, &mut output);
+    output
+}
+
+fn disassemble_function(function: &FunctionProto, output: &mut String) {
+    let name = if function.name.is_empty() {
+        "<script>"
+    } else {
+        &function.name
+    };
+    writeln!(output, "== {} ==", name).unwrap();
+
+    let chunk = &function.chunk;
+    writeln!(output, "constants:").unwrap();
+    for (index, constant) in chunk.constants.iter().enumerate() {
+        writeln!(output, "{:4} {}", index, constant).unwrap();
+    }
+
+    writeln!(output, "code:").unwrap();
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, output);
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(nested) = constant {
+            writeln!(output).unwrap();
+            disassemble_function(nested, output);
+        }
+    }
+}
+
+// writes the instruction at offset and returns the offset of the next one
+fn disassemble_instruction(chunk: &Chunk, offset: usize, output: &mut String) -> usize {
+    write!(output, "{:04} ", offset).unwrap();
+    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        write!(output, "   | ").unwrap();
+    } else {
+        write!(output, "{:4} ", chunk.lines[offset]).unwrap();
+    }
+
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => {
+            writeln!(output, "Unknown opcode {}", chunk.code[offset]).unwrap();
+            return offset + 1;
+        }
+    };
+
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index = chunk.read_u16(offset + 1);
+            let constant = &chunk.constants[index as usize];
+            writeln!(
+                output,
+                "{:<16} {:4} {}",
+                format!("{:?}", op),
+                index,
+                constant
+            )
+            .unwrap();
+            offset + 3
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call
+        | OpCode::Interpolate => {
+            let operand = chunk.code[offset + 1];
+            writeln!(output, "{:<16} {:4}", format!("{:?}", op), operand).unwrap();
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let target = if op == OpCode::Loop {
+                offset + 3 - jump
+            } else {
+                offset + 3 + jump
+            };
+            writeln!(
+                output,
+                "{:<16} {:4} -> {:04}",
+                format!("{:?}", op),
+                jump,
+                target
+            )
+            .unwrap();
+            offset + 3
+        }
+        OpCode::Closure => {
+            let index = chunk.read_u16(offset + 1);
+            let constant = &chunk.constants[index as usize];
+            writeln!(output, "{:<16} {:4} {}", "Closure", index, constant).unwrap();
+
+            let mut offset = offset + 3;
+            if let Constant::Function(function) = constant {
+                for _ in 0..function.upvalue_count {
+                    let kind = if chunk.code[offset] == 1 {
+                        "local"
+                    } else {
+                        "upvalue"
+                    };
+                    writeln!(
+                        output,
+                        "{:04}    |   {:<14} {:4}",
+                        offset,
+                        kind,
+                        chunk.code[offset + 1]
+                    )
+                    .unwrap();
+                    offset += 2;
+                }
+            }
+            offset
+        }
+        _ => {
+            writeln!(output, "{:?}", op).unwrap();
+            offset + 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::parser::parse;
+    use crate::resolver::resolve;
+    use crate::scanner;
+
+    fn disassemble_source(input: &str) -> String {
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        disassemble(&compile(&statements).unwrap())
+    }
+
+    #[test]
+    fn test_disassemble_constants_and_lines() {
+        assert_eq!(
+            disassemble_source("var a = 1;\nprint a;"),
+            "== <script> ==
+constants:
+   0 1
+   1 \"a\"
+code:
+0000    1 Constant            0 1
+0003    | DefineGlobal        1 \"a\"
+0006    2 GetGlobal           1 \"a\"
+0009    | Print
+0010    | Nil
+0011    | Return
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_lines_of_every_statement() {
+        assert_eq!(
+            disassemble_source("print 1;\n\n\nprint \"a\" + 2;\nif (true) {\n  print nil;\n}"),
+            "== <script> ==
+constants:
+   0 1
+   1 \"a\"
+   2 2
+code:
+0000    1 Constant            0 1
+0003    | Print
+0004    4 Constant            1 \"a\"
+0007    | Constant            2 2
+0010    | Add
+0011    | Print
+0012    5 True
+0013    | JumpIfFalse         6 -> 0022
+0016    | Pop
+0017    6 Nil
+0018    | Print
+0019    | Jump                1 -> 0023
+0022    | Pop
+0023    | Nil
+0024    | Return
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_jumps() {
+        let output = disassemble_source("while (true) print 1;");
+        assert!(output.contains("0001    | JumpIfFalse         8 -> 0012\n"));
+        assert!(output.contains("0009    | Loop               12 -> 0000\n"));
+    }
+
+    #[test]
+    fn test_disassemble_nested_functions() {
+        let output = disassemble_source(
+            "fun outer() {
+                var x = 1;
+                fun inner() {
+                    return x;
+                }
+                return inner;
+            }",
+        );
+        assert!(output.contains("== <script> ==\n"));
+        assert!(output.contains("\n== outer ==\n"));
+        assert!(output.contains("\n== inner ==\n"));
+        assert!(output.contains("Closure             1 <fn inner>\n"));
+        assert!(output.contains("|   local             1\n"));
+        assert!(output.contains("GetUpvalue          0\n"));
+    }
+}
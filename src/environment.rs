@@ -1,58 +1,69 @@
-use crate::interpreter::{RuntimeError, Value};
+use crate::interpreter::Value;
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 
+// The values of one local scope, in the order the resolver assigned their slots. Globals live in
+// the interpreter's own table instead.
 #[derive(Clone, PartialEq, Debug)]
 pub struct Environment {
-    bindings: HashMap<String, Value>,
+    slots: Vec<Value>,
     pub enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Rc<RefCell<Self>> {
-        let bindings = HashMap::new();
         Rc::new(RefCell::new(Environment {
-            bindings,
+            slots: vec![],
             enclosing: None,
         }))
     }
 
     pub fn new_with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Rc<RefCell<Self>> {
-        let bindings = HashMap::new();
         Rc::new(RefCell::new(Environment {
-            bindings,
+            slots: vec![],
             enclosing: Some(enclosing),
         }))
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
-        self.bindings.insert(name, value);
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.slots.iter()
     }
 
-    pub fn get(&self, name: String) -> Option<Value> {
-        let current = self.bindings.get(&name);
+    // drops everything the environment refers to, for the garbage collector to break cycles
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.enclosing = None;
+    }
+
+    // declarations run in the order they appear, so each one fills the next slot
+    pub fn define(&mut self, value: Value) {
+        self.slots.push(value);
+    }
 
-        if let Some(v) = current {
-            return Some(v.clone());
-        } else {
-            if let Some(enclosing) = self.enclosing.as_ref() {
-                return enclosing.borrow().get(name);
-            } else {
-                None
-            }
+    // looks only in the environment `depth` steps up the enclosing chain
+    pub fn get_at(&self, depth: usize, slot: usize) -> Option<Value> {
+        if depth == 0 {
+            return self.slots.get(slot).cloned();
         }
+        self.enclosing
+            .as_ref()
+            .and_then(|enclosing| enclosing.borrow().get_at(depth - 1, slot))
     }
 
-    pub fn assign(&mut self, name: String, value: Value) -> Result<(), RuntimeError> {
-        if self.bindings.contains_key(&name) {
-            self.bindings.insert(name.clone(), value);
-            Ok(())
-        } else {
-            match self.enclosing.as_mut() {
-                None => Err(RuntimeError::UndefinedVariable(name)),
-                Some(env) => env.borrow_mut().assign(name, value),
-            }
+    // returns false if there is no such slot
+    pub fn assign_at(&mut self, depth: usize, slot: usize, value: Value) -> bool {
+        if depth == 0 {
+            return match self.slots.get_mut(slot) {
+                Some(current) => {
+                    *current = value;
+                    true
+                }
+                None => false,
+            };
+        }
+        match self.enclosing.as_ref() {
+            None => false,
+            Some(enclosing) => enclosing.borrow_mut().assign_at(depth - 1, slot, value),
         }
     }
 }
@@ -62,95 +73,35 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_define() {
-        let mut env = Environment::new();
-        env.borrow_mut()
-            .define(String::from("a"), Value::Number(1.0));
-        assert_eq!(
-            env.borrow().get(String::from("a")),
-            Some(Value::Number(1.0))
-        );
-    }
-
-    #[test]
-    fn test_get() {
-        let mut env = Environment::new();
-        env.borrow_mut()
-            .define(String::from("a"), Value::Number(1.0));
-        assert_eq!(
-            env.borrow().get(String::from("a")),
-            Some(Value::Number(1.0))
-        );
-    }
-
-    #[test]
-    fn test_get_enclosing() {
-        let mut env = Environment::new();
-        env.borrow_mut()
-            .define(String::from("a"), Value::Number(1.0));
-        let mut env2 = Environment::new_with_enclosing(env);
-        assert_eq!(
-            env2.borrow().get(String::from("a")),
-            Some(Value::Number(1.0))
-        );
-    }
-
-    #[test]
-    fn test_assign_enclosing() {
-        let mut env = Environment::new();
-        env.borrow_mut()
-            .define(String::from("a"), Value::Number(1.0));
-        let mut env2 = Environment::new_with_enclosing(env.clone());
-        env2.borrow_mut()
-            .assign(String::from("a"), Value::Number(2.0))
-            .unwrap();
-        assert_eq!(
-            env2.borrow().get(String::from("a")),
-            Some(Value::Number(2.0))
-        );
+    fn test_define_fills_slots_in_order() {
+        let env = Environment::new();
+        env.borrow_mut().define(Value::Number(1.0));
+        env.borrow_mut().define(Value::Number(2.0));
+        assert_eq!(env.borrow().get_at(0, 0), Some(Value::Number(1.0)));
+        assert_eq!(env.borrow().get_at(0, 1), Some(Value::Number(2.0)));
+        assert_eq!(env.borrow().get_at(0, 2), None);
     }
 
     #[test]
-    fn test_assign_enclosing_undefined() {
+    fn test_get_at() {
         let env = Environment::new();
-        env.borrow_mut()
-            .define(String::from("a"), Value::Number(1.0));
+        env.borrow_mut().define(Value::Number(1.0));
         let env2 = Environment::new_with_enclosing(env.clone());
-        env2.borrow_mut()
-            .define(String::from("b"), Value::Number(2.0));
-        assert_eq!(
-            env2.borrow().get(String::from("b")),
-            Some(Value::Number(2.0))
-        );
-        assert_eq!(
-            env2.borrow().get(String::from("a")),
-            Some(Value::Number(1.0))
-        );
-        assert_eq!(
-            env.borrow().get(String::from("a")),
-            Some(Value::Number(1.0))
-        );
-        let result = env2
-            .borrow_mut()
-            .assign(String::from("a"), Value::Number(3.0));
-        assert!(result.is_ok());
-        assert_eq!(
-            env.borrow().get(String::from("a")),
-            Some(Value::Number(3.0))
-        );
+        env2.borrow_mut().define(Value::Number(2.0));
+        assert_eq!(env2.borrow().get_at(0, 0), Some(Value::Number(2.0)));
+        assert_eq!(env2.borrow().get_at(1, 0), Some(Value::Number(1.0)));
+        assert_eq!(env2.borrow().get_at(2, 0), None);
     }
 
     #[test]
-    fn test_assign() {
-        let mut env = Environment::new();
-        env.borrow_mut()
-            .define(String::from("a"), Value::Number(1.0));
-        env.borrow_mut()
-            .assign(String::from("a"), Value::Number(2.0))
-            .unwrap();
-        assert_eq!(
-            env.borrow().get(String::from("a")),
-            Some(Value::Number(2.0))
-        );
+    fn test_assign_at() {
+        let env = Environment::new();
+        env.borrow_mut().define(Value::Number(1.0));
+        let env2 = Environment::new_with_enclosing(env.clone());
+        env2.borrow_mut().define(Value::Number(2.0));
+        assert!(env2.borrow_mut().assign_at(1, 0, Value::Number(3.0)));
+        assert_eq!(env.borrow().get_at(0, 0), Some(Value::Number(3.0)));
+        assert_eq!(env2.borrow().get_at(0, 0), Some(Value::Number(2.0)));
+        assert!(!env2.borrow_mut().assign_at(0, 1, Value::Nil));
     }
 }
@@ -0,0 +1,356 @@
+use crate::environment::Environment;
+use crate::interpreter::{Class, Instance, Value};
+use crate::symbol::Symbol;
+use crate::vm;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+// objects tracked before the first collection, and the least the threshold is ever set to
+pub const INITIAL_THRESHOLD: usize = 1024;
+
+enum Object {
+    Environment(Weak<RefCell<Environment>>),
+    Instance(Weak<RefCell<Instance>>),
+    Upvalue(Weak<RefCell<vm::Upvalue>>),
+    VmInstance(Weak<RefCell<vm::Instance>>),
+    VmClass(Weak<RefCell<vm::Class>>),
+}
+
+impl Object {
+    fn is_live(&self) -> bool {
+        match self {
+            Object::Environment(env) => env.strong_count() > 0,
+            Object::Instance(instance) => instance.strong_count() > 0,
+            Object::Upvalue(upvalue) => upvalue.strong_count() > 0,
+            Object::VmInstance(instance) => instance.strong_count() > 0,
+            Object::VmClass(class) => class.strong_count() > 0,
+        }
+    }
+}
+
+// A function is stored in the environment it closes over, and an instance can be stored in its own
+// fields, so reference counting alone never frees them. The heap keeps a weak reference to every
+// environment and instance it allocates, so a collection can find the ones that are no longer
+// reachable from the roots and empty them, breaking the cycles that keep them alive.
+// The vm's cycles run through the same kinds of object: closed upvalues, which can hold the
+// closure that captured them, instance fields, and the methods of a class.
+pub struct Heap {
+    objects: Vec<Object>,
+    // when to drop the weak references to objects that have already been freed
+    next_prune: usize,
+    // when a collection is due at the next safe point
+    next_collection: usize,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            objects: vec![],
+            next_prune: INITIAL_THRESHOLD,
+            next_collection: INITIAL_THRESHOLD,
+        }
+    }
+
+    pub fn environment(&mut self, enclosing: Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let env = Environment::new_with_enclosing(enclosing);
+        self.track(Object::Environment(Rc::downgrade(&env)));
+        env
+    }
+
+    pub fn instance(&mut self, class: Rc<Class>) -> Rc<RefCell<Instance>> {
+        let instance = Rc::new(RefCell::new(Instance {
+            class,
+            fields: HashMap::new(),
+        }));
+        self.track(Object::Instance(Rc::downgrade(&instance)));
+        instance
+    }
+
+    pub fn upvalue(&mut self, slot: usize) -> Rc<RefCell<vm::Upvalue>> {
+        let upvalue = Rc::new(RefCell::new(vm::Upvalue::Open(slot)));
+        self.track(Object::Upvalue(Rc::downgrade(&upvalue)));
+        upvalue
+    }
+
+    pub fn vm_instance(&mut self, class: Rc<RefCell<vm::Class>>) -> Rc<RefCell<vm::Instance>> {
+        let instance = Rc::new(RefCell::new(vm::Instance {
+            class,
+            fields: HashMap::new(),
+        }));
+        self.track(Object::VmInstance(Rc::downgrade(&instance)));
+        instance
+    }
+
+    pub fn vm_class(&mut self, name: Symbol) -> Rc<RefCell<vm::Class>> {
+        let class = Rc::new(RefCell::new(vm::Class {
+            name,
+            methods: HashMap::new(),
+        }));
+        self.track(Object::VmClass(Rc::downgrade(&class)));
+        class
+    }
+
+    // the number of tracked objects that have not been freed yet
+    #[cfg(test)]
+    pub fn live_objects(&self) -> usize {
+        self.objects
+            .iter()
+            .filter(|object| object.is_live())
+            .count()
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.objects.len() >= self.next_collection
+    }
+
+    // Empties every object that can't be reached from the roots. The roots must cover everything
+    // the caller can still use: the current environment, every environment a block or call will
+    // return to, the globals, and any value evaluated but not yet stored. An object only held by
+    // a Rust local that isn't among the roots is emptied while it is still in use.
+    pub fn collect<'a>(
+        &mut self,
+        environments: &[Rc<RefCell<Environment>>],
+        values: impl Iterator<Item = &'a Value>,
+    ) {
+        let marked = mark(environments, values);
+        self.sweep(&marked);
+    }
+
+    // The vm's roots are the values on its stack, its globals and the closures of its call
+    // frames. Open upvalues point into the stack, so they never need emptying.
+    pub fn collect_vm<'a>(
+        &mut self,
+        values: impl Iterator<Item = &'a vm::Value>,
+        closures: impl Iterator<Item = &'a Rc<vm::Closure>>,
+    ) {
+        let marked = mark_vm(values, closures);
+        self.sweep(&marked);
+    }
+
+    fn sweep(&mut self, marked: &HashSet<usize>) {
+        for object in &self.objects {
+            match object {
+                Object::Environment(env) => {
+                    if let Some(env) = env.upgrade() {
+                        if !marked.contains(&(Rc::as_ptr(&env) as usize)) {
+                            env.borrow_mut().clear();
+                        }
+                    }
+                }
+                Object::Instance(instance) => {
+                    if let Some(instance) = instance.upgrade() {
+                        if !marked.contains(&(Rc::as_ptr(&instance) as usize)) {
+                            instance.borrow_mut().fields.clear();
+                        }
+                    }
+                }
+                Object::Upvalue(upvalue) => {
+                    if let Some(upvalue) = upvalue.upgrade() {
+                        if !marked.contains(&(Rc::as_ptr(&upvalue) as usize)) {
+                            if let vm::Upvalue::Closed(value) = &mut *upvalue.borrow_mut() {
+                                *value = vm::Value::Nil;
+                            }
+                        }
+                    }
+                }
+                Object::VmInstance(instance) => {
+                    if let Some(instance) = instance.upgrade() {
+                        if !marked.contains(&(Rc::as_ptr(&instance) as usize)) {
+                            instance.borrow_mut().fields.clear();
+                        }
+                    }
+                }
+                Object::VmClass(class) => {
+                    if let Some(class) = class.upgrade() {
+                        if !marked.contains(&(Rc::as_ptr(&class) as usize)) {
+                            class.borrow_mut().methods.clear();
+                        }
+                    }
+                }
+            }
+        }
+
+        self.prune();
+        self.next_collection = INITIAL_THRESHOLD.max(self.objects.len() * 2);
+    }
+
+    fn track(&mut self, object: Object) {
+        self.objects.push(object);
+        if self.objects.len() >= self.next_prune {
+            self.prune();
+        }
+    }
+
+    fn prune(&mut self) {
+        self.objects.retain(|object| object.is_live());
+        self.next_prune = INITIAL_THRESHOLD.max(self.objects.len() * 2);
+    }
+}
+
+// the addresses of every environment and instance reachable from the roots
+fn mark<'a>(
+    roots: &[Rc<RefCell<Environment>>],
+    root_values: impl Iterator<Item = &'a Value>,
+) -> HashSet<usize> {
+    let mut marked = HashSet::new();
+    let mut environments: Vec<Rc<RefCell<Environment>>> = roots.to_vec();
+    let mut values: Vec<Value> = root_values.cloned().collect();
+
+    while !environments.is_empty() || !values.is_empty() {
+        if let Some(env) = environments.pop() {
+            if marked.insert(Rc::as_ptr(&env) as usize) {
+                let env = env.borrow();
+                values.extend(env.values().cloned());
+                environments.extend(env.enclosing.clone());
+            }
+            continue;
+        }
+
+        match values.pop() {
+            Some(Value::Function { closure, .. }) => environments.push(closure),
+            Some(Value::Class(class)) => trace_class(&class, &mut values),
+            Some(Value::Instance(instance)) if marked.insert(Rc::as_ptr(&instance) as usize) => {
+                let instance = instance.borrow();
+                values.extend(instance.fields.values().cloned());
+                trace_class(&instance.class, &mut values);
+            }
+            _ => {}
+        }
+    }
+    marked
+}
+
+// the addresses of every upvalue, instance and class reachable from the vm's roots, along with
+// the closures passed through on the way
+fn mark_vm<'a>(
+    root_values: impl Iterator<Item = &'a vm::Value>,
+    root_closures: impl Iterator<Item = &'a Rc<vm::Closure>>,
+) -> HashSet<usize> {
+    let mut marked = HashSet::new();
+    let mut values: Vec<vm::Value> = root_values.cloned().collect();
+    values.extend(root_closures.cloned().map(vm::Value::Closure));
+
+    while let Some(value) = values.pop() {
+        match value {
+            vm::Value::Closure(closure) if marked.insert(Rc::as_ptr(&closure) as usize) => {
+                for upvalue in &closure.upvalues {
+                    if marked.insert(Rc::as_ptr(upvalue) as usize) {
+                        if let vm::Upvalue::Closed(value) = &*upvalue.borrow() {
+                            values.push(value.clone());
+                        }
+                    }
+                }
+            }
+            vm::Value::BoundMethod(bound) => {
+                values.push(bound.receiver.clone());
+                values.push(vm::Value::Closure(bound.method.clone()));
+            }
+            vm::Value::Class(class) if marked.insert(Rc::as_ptr(&class) as usize) => {
+                let class = class.borrow();
+                values.extend(class.methods.values().cloned().map(vm::Value::Closure));
+            }
+            vm::Value::Instance(instance) if marked.insert(Rc::as_ptr(&instance) as usize) => {
+                let instance = instance.borrow();
+                values.extend(instance.fields.values().cloned());
+                values.push(vm::Value::Class(instance.class.clone()));
+            }
+            _ => {}
+        }
+    }
+    marked
+}
+
+// classes are immutable, so they are never part of a cycle themselves and aren't tracked
+fn trace_class(class: &Class, values: &mut Vec<Value>) {
+    values.extend(class.methods.values().cloned());
+    if let Some(superclass) = &class.superclass {
+        trace_class(superclass, values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+    use crate::parser::parse;
+    use crate::resolver::resolve;
+    use crate::scanner;
+
+    fn run(interpreter: &mut Interpreter, input: &str) {
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        interpreter.evaluate(&statements).unwrap();
+    }
+
+    #[test]
+    fn test_unreachable_closures_are_collected() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }",
+        );
+        interpreter.collect_garbage();
+        let baseline = interpreter.live_objects();
+
+        // each counter closes over the call's environment, which holds the counter itself
+        for _ in 0..100 {
+            run(&mut interpreter, "var counter = makeCounter(); counter();");
+        }
+        assert!(interpreter.live_objects() > baseline + 2);
+
+        // only the environment of the last counter is still reachable
+        interpreter.collect_garbage();
+        assert_eq!(interpreter.live_objects(), baseline + 1);
+        // reading a cleared environment would fail with an undefined variable
+        run(&mut interpreter, "counter();");
+    }
+
+    #[test]
+    fn test_unreachable_instances_are_collected() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "class Node {
+                init() {
+                    this.self = this;
+                    this.method = this.describe;
+                }
+                describe() {
+                    return \"node\";
+                }
+            }
+            var kept = Node();",
+        );
+        interpreter.collect_garbage();
+        let baseline = interpreter.live_objects();
+
+        for _ in 0..100 {
+            run(&mut interpreter, "Node();");
+        }
+        interpreter.collect_garbage();
+        assert_eq!(interpreter.live_objects(), baseline);
+
+        run(&mut interpreter, "kept.self.method();");
+    }
+
+    #[test]
+    fn test_long_sessions_collect_automatically() {
+        let mut interpreter = Interpreter::new();
+        run(
+            &mut interpreter,
+            "fun loop() { fun again() { return again; } }",
+        );
+        for _ in 0..10 * super::INITIAL_THRESHOLD {
+            run(&mut interpreter, "loop();");
+        }
+        assert!(interpreter.live_objects() <= 2 * super::INITIAL_THRESHOLD);
+    }
+}
@@ -1,33 +1,146 @@
 use crate::environment::Environment;
-use crate::parser::{BinaryOperator, Expr, LiteralValue, Statement};
+use crate::gc::Heap;
+use crate::parser::{
+    BinaryOperator, Expr, FunctionDecl, LiteralValue, Local, LogicalOperator, Statement,
+    UnaryOperator,
+};
+use crate::scanner::Token;
+use crate::symbol::Symbol;
 use std::cell::RefCell;
+use std::collections::HashMap;
 
 use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
+// Calls nest no deeper than this, the same limit as the vm's call frames
+const MAX_CALL_DEPTH: usize = 1024;
+
+// Every call recurses through the evaluator on the Rust stack, taking tens of kilobytes in a
+// debug build, so programs run on a thread with room for MAX_CALL_DEPTH of them
+pub const STACK_SIZE: usize = MAX_CALL_DEPTH * 64 * 1024;
+
 #[derive(PartialEq, Debug)]
-pub enum RuntimeError {
-    Runtime { message: String },
+pub enum RuntimeErrorType {
+    Runtime {
+        message: String,
+    },
     InvalidFunction,
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        got: usize,
+    },
     UndefinedVariable(String),
-    Return(Value),
+    OperandMustBeNumber(UnaryOperator, String),
 }
 
-impl Display for RuntimeError {
+impl Display for RuntimeErrorType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            RuntimeError::Runtime { message } => write!(f, "Runtime error: {}", message),
-            RuntimeError::InvalidFunction => write!(f, "Invalid function"),
-            RuntimeError::UndefinedVariable(name) => write!(f, "Undefined variable {}", name),
-            RuntimeError::Return(value) => write!(f, "Return {}", value),
+            RuntimeErrorType::Runtime { message } => write!(f, "{}", message),
+            RuntimeErrorType::InvalidFunction => write!(f, "Invalid function"),
+            RuntimeErrorType::ArityMismatch {
+                function,
+                expected,
+                got,
+            } => write!(
+                f,
+                "{}() expected {} argument{} but got {}",
+                function,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                got
+            ),
+            RuntimeErrorType::UndefinedVariable(name) => write!(f, "Undefined variable {}", name),
+            RuntimeErrorType::OperandMustBeNumber(op, value) => {
+                write!(f, "Operand of {} must be a number, got {}", op, value)
+            }
+        }
+    }
+}
+
+// A call to the named function that was still running when the error happened
+#[derive(PartialEq, Debug, Clone)]
+pub struct CallSite {
+    pub function: String,
+    pub line: usize,
+}
+
+// The call stack runs from the innermost call outwards and is filled in as the error unwinds
+#[derive(PartialEq, Debug)]
+pub struct RuntimeError {
+    pub error_type: RuntimeErrorType,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub call_stack: Vec<CallSite>,
+}
+
+impl RuntimeError {
+    pub fn new(error_type: RuntimeErrorType, token: &Token) -> Self {
+        RuntimeError {
+            error_type,
+            line: token.line,
+            column: token.column,
+            length: token.length(),
+            call_stack: vec![],
         }
     }
+
+    fn called_from(mut self, function: &str, line: usize) -> Self {
+        self.call_stack.push(CallSite {
+            function: String::from(function),
+            line,
+        });
+        self
+    }
+
+    // one line per frame, innermost call first
+    pub fn traceback(&self) -> Vec<String> {
+        let function_name = |index: usize| match self.call_stack.get(index) {
+            Some(call) => format!("{}()", call.function),
+            None => String::from("script"),
+        };
+        let mut frames = vec![format!(
+            "[line {} column {}] in {}",
+            self.line,
+            self.column,
+            function_name(0)
+        )];
+        for (index, call) in self.call_stack.iter().enumerate() {
+            frames.push(format!(
+                "[line {}] in {}",
+                call.line,
+                function_name(index + 1)
+            ));
+        }
+        frames
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error_type)?;
+        for frame in self.traceback() {
+            write!(f, "\n    {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+// How a statement finished. Non-local exits unwind through evaluate_statement separately from
+// runtime errors, until the construct that handles them is reached.
+#[derive(PartialEq, Debug)]
+pub enum ControlFlow {
+    Normal,
+    Return(Value),
 }
 
 #[derive(Clone)]
 pub struct NativeFunction {
     pub name: String,
-    pub callable: fn(args: &[Value]) -> Result<Value, RuntimeError>,
+    pub arity: usize,
+    pub callable: fn(args: &[Value]) -> Result<Value, RuntimeErrorType>,
 }
 
 impl std::fmt::Debug for NativeFunction {
@@ -45,16 +158,66 @@ impl PartialEq for NativeFunction {
 #[derive(PartialEq, Clone, Debug)]
 pub enum Value {
     Number(f64),
-    String(String),
+    String(Rc<str>),
     Bool(bool),
     Nil,
     NativeFunction(NativeFunction),
     Function {
-        name: String,
-        params: Vec<String>,
-        body: Vec<Statement>,
+        declaration: Rc<FunctionDecl>,
         closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
     },
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+pub struct Class {
+    pub name: Symbol,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<Symbol, Value>,
+}
+
+impl Class {
+    fn find_method(&self, name: Symbol) -> Option<Value> {
+        match self.methods.get(&name) {
+            Some(method) => Some(method.clone()),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+}
+
+// classes and instances are compared by identity, and their methods and fields can refer back to
+// themselves, so neither derives PartialEq or Debug
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "class {}", self.name)
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<Symbol, Value>,
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
 }
 
 impl Display for Value {
@@ -65,22 +228,39 @@ impl Display for Value {
             Value::Bool(b) => write!(f, "{}", b),
             Value::Nil => write!(f, "nil"),
             Value::NativeFunction(nf) => write!(f, "{}", nf.name),
-            Value::Function { name, .. } => write!(f, "function {}()", name),
+            Value::Function { declaration, .. } => {
+                write!(f, "function {}()", declaration.name.symbol())
+            }
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
         }
     }
 }
 
+// Locals live in environments addressed by the slots the resolver assigned, while globals are
+// looked up by name in their own table
 pub struct Interpreter {
     env: Rc<RefCell<Environment>>,
+    // the environment at the top level, which never holds any locals itself
+    top_level: Rc<RefCell<Environment>>,
+    globals: HashMap<Symbol, Value>,
+    heap: Heap,
+    // Environments that blocks and calls will return to, and values an expression has evaluated
+    // but not used yet. Both are only held on the Rust stack otherwise, so they are roots for
+    // collections that happen part way through a statement.
+    suspended: Vec<Rc<RefCell<Environment>>>,
+    temporaries: Vec<Value>,
+    call_depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut env = Environment::new();
-        env.borrow_mut().define(
-            String::from("clock"),
+        let mut globals = HashMap::new();
+        globals.insert(
+            Symbol::intern("clock"),
             Value::NativeFunction(NativeFunction {
                 name: String::from("clock"),
+                arity: 0,
                 callable: |_| {
                     Ok(Value::Number(
                         std::time::SystemTime::now()
@@ -92,151 +272,414 @@ impl Interpreter {
             }),
         );
 
-        Interpreter { env }
+        let env = Environment::new();
+        Interpreter {
+            top_level: env.clone(),
+            env,
+            globals,
+            heap: Heap::new(),
+            suspended: vec![],
+            temporaries: vec![],
+            call_depth: 0,
+        }
     }
 
-    pub fn evaluate(&mut self, statements: &Vec<Statement>) -> Result<(), RuntimeError> {
+    pub fn evaluate(&mut self, statements: &[Statement]) -> Result<(), RuntimeError> {
+        // the resolver rejects return outside of a function, so a top level program ends normally
         for statement in statements {
-            match self.evaluate_statement(&statement) {
-                Ok(_) => {}
-                Err(err) => return Err(err),
+            let result = self.evaluate_statement(statement);
+            // between top level statements nothing outside the globals holds on to a value, so
+            // it is safe to collect
+            if self.heap.should_collect() {
+                self.collect_garbage();
             }
+            result?;
         }
         Ok(())
     }
 
+    pub fn collect_garbage(&mut self) {
+        let mut environments = vec![self.env.clone()];
+        environments.extend(self.suspended.iter().cloned());
+        self.heap.collect(
+            &environments,
+            self.globals.values().chain(self.temporaries.iter()),
+        );
+    }
+
+    // Entering a block or call and starting a loop iteration are safe points too, so a program
+    // that runs inside one top level statement is still collected
+    fn safe_point(&mut self) {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+    }
+
+    // for checking that long running sessions don't keep growing
+    #[cfg(test)]
+    pub fn live_objects(&self) -> usize {
+        self.heap.live_objects()
+    }
+
+    fn evaluate_statements(
+        &mut self,
+        statements: &[Statement],
+    ) -> Result<ControlFlow, RuntimeError> {
+        for statement in statements {
+            match self.evaluate_statement(statement)? {
+                ControlFlow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+
+    // runs statements in env, restoring the current environment however they finish, since
+    // resolved depths rely on the enclosing scope being the same afterwards
+    fn execute_block(
+        &mut self,
+        statements: &[Statement],
+        env: Rc<RefCell<Environment>>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let previous = std::mem::replace(&mut self.env, env);
+        self.suspended.push(previous.clone());
+        self.safe_point();
+        let result = self.evaluate_statements(statements);
+        self.suspended.pop();
+        self.env = previous;
+        result
+    }
+
+    // evaluates expr while keeping value, which was evaluated before it, reachable, and hands
+    // value back alongside the result
+    fn evaluate_rooted(
+        &mut self,
+        value: Value,
+        expr: &Expr,
+    ) -> Result<(Value, Value), RuntimeError> {
+        self.temporaries.push(value);
+        let result = self.evaluate_expression(expr);
+        let value = self
+            .temporaries
+            .pop()
+            .expect("temporaries are pushed and popped in pairs");
+        result.map(|result| (value, result))
+    }
+
     fn evaluate_binary_op(
         &mut self,
         left: &Expr,
         right: &Expr,
         op: &BinaryOperator,
+        operator: &Token,
     ) -> Result<Value, RuntimeError> {
         let l = self.evaluate_expression(left)?;
-        let r = self.evaluate_expression(right)?;
+        let (l, r) = self.evaluate_rooted(l, right)?;
 
         match (l, op, r) {
-            (Value::Number(a), BinaryOperator::Plus, Value::Number(b)) => {
-                return Ok(Value::Number(a + b))
-            }
-            (Value::Number(a), BinaryOperator::Minus, Value::Number(b)) => {
-                return Ok(Value::Number(a - b))
+            (l, BinaryOperator::EqualEqual, r) => Ok(Value::Bool(values_equal(&l, &r))),
+            (l, BinaryOperator::BangEqual, r) => Ok(Value::Bool(!values_equal(&l, &r))),
+            (Value::Number(a), BinaryOperator::Plus, Value::Number(b)) => Ok(Value::Number(a + b)),
+            // when one side of + is a string, numbers are converted using their printed form
+            (Value::String(a), BinaryOperator::Plus, Value::String(b)) => {
+                Ok(Value::String(Rc::from(format!("{}{}", a, b))))
             }
-            (Value::Number(a), BinaryOperator::Star, Value::Number(b)) => {
-                return Ok(Value::Number(a * b))
+            (Value::String(a), BinaryOperator::Plus, Value::Number(b)) => {
+                Ok(Value::String(Rc::from(format!("{}{}", a, b))))
             }
-            (Value::Number(a), BinaryOperator::Slash, Value::Number(b)) => {
-                return Ok(Value::Number(a / b))
-            }
-            (Value::Bool(a), BinaryOperator::And, Value::Bool(b)) => {
-                return Ok(Value::Bool(a && b))
-            }
-            (Value::Bool(a), BinaryOperator::Or, Value::Bool(b)) => {
-                return Ok(Value::Bool(a || b));
-            }
-            (Value::Number(a), BinaryOperator::Greater, Value::Number(b)) => {
-                return Ok(Value::Bool(a > b))
+            (Value::Number(a), BinaryOperator::Plus, Value::String(b)) => {
+                Ok(Value::String(Rc::from(format!("{}{}", a, b))))
             }
+            (Value::Number(a), BinaryOperator::Minus, Value::Number(b)) => Ok(Value::Number(a - b)),
+            (Value::Number(a), BinaryOperator::Star, Value::Number(b)) => Ok(Value::Number(a * b)),
+            (Value::Number(a), BinaryOperator::Slash, Value::Number(b)) => Ok(Value::Number(a / b)),
+            (Value::Number(a), BinaryOperator::Greater, Value::Number(b)) => Ok(Value::Bool(a > b)),
             (Value::Number(a), BinaryOperator::GreaterEqual, Value::Number(b)) => {
-                return Ok(Value::Bool(a >= b))
-            }
-            (Value::Number(a), BinaryOperator::Less, Value::Number(b)) => {
-                return Ok(Value::Bool(a < b))
+                Ok(Value::Bool(a >= b))
             }
+            (Value::Number(a), BinaryOperator::Less, Value::Number(b)) => Ok(Value::Bool(a < b)),
             (Value::Number(a), BinaryOperator::LessEqual, Value::Number(b)) => {
-                return Ok(Value::Bool(a <= b))
+                Ok(Value::Bool(a <= b))
             }
-            (Value::Number(a), BinaryOperator::EqualEqual, Value::Number(b)) => {
-                return Ok(Value::Bool(a == b))
+            (Value::String(a), BinaryOperator::Greater, Value::String(b)) => Ok(Value::Bool(a > b)),
+            (Value::String(a), BinaryOperator::GreaterEqual, Value::String(b)) => {
+                Ok(Value::Bool(a >= b))
+            }
+            (Value::String(a), BinaryOperator::Less, Value::String(b)) => Ok(Value::Bool(a < b)),
+            (Value::String(a), BinaryOperator::LessEqual, Value::String(b)) => {
+                Ok(Value::Bool(a <= b))
             }
             (l, op, r) => {
                 let error = format!("Invalid operation: {} {} {}", l, op, r);
-                Err(RuntimeError::Runtime {
-                    message: String::from(error),
-                })
+                Err(RuntimeError::new(
+                    RuntimeErrorType::Runtime { message: error },
+                    operator,
+                ))
             }
         }
     }
 
     fn evaluate_expression(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
-            Expr::Binary(left, right, op) => self.evaluate_binary_op(left, right, op),
-            Expr::Unary(_expr, _op) => {
-                todo!()
+            Expr::Binary(left, right, op, operator) => {
+                self.evaluate_binary_op(left, right, op, operator)
+            }
+            Expr::Logical(left, right, op) => {
+                let left = self.evaluate_expression(left)?;
+                // the left operand is returned as is if it decides the result
+                let short_circuit = match op {
+                    LogicalOperator::Or => is_truthy(&left),
+                    LogicalOperator::And => !is_truthy(&left),
+                };
+                if short_circuit {
+                    Ok(left)
+                } else {
+                    self.evaluate_expression(right)
+                }
             }
-            Expr::Literal(lit) => match lit {
+            Expr::Unary(expr, op, operator) => {
+                let value = self.evaluate_expression(expr)?;
+                match (op, value) {
+                    (UnaryOperator::Bang, value) => Ok(Value::Bool(!is_truthy(&value))),
+                    (UnaryOperator::Minus, Value::Number(num)) => Ok(Value::Number(-num)),
+                    (UnaryOperator::Minus, value) => Err(RuntimeError::new(
+                        RuntimeErrorType::OperandMustBeNumber(
+                            UnaryOperator::Minus,
+                            value.to_string(),
+                        ),
+                        operator,
+                    )),
+                }
+            }
+            Expr::Literal(lit, _) => match lit {
                 LiteralValue::Number(num) => Ok(Value::Number(*num)),
                 LiteralValue::String(str) => Ok(Value::String(str.clone())),
                 LiteralValue::Boolean(bool) => Ok(Value::Bool(*bool)),
                 LiteralValue::Nil => Ok(Value::Nil),
             },
-            Expr::Variable(token) => {
-                let name = String::from_utf8(token.lexeme.clone()).unwrap();
-                return match self.env.borrow().get(name) {
-                    None => Ok(Value::Nil),
+            Expr::Variable(token, local) => {
+                match self.look_up_variable(token.symbol(), local.get()) {
+                    None => Err(RuntimeError::new(
+                        RuntimeErrorType::UndefinedVariable(token.symbol().to_string()),
+                        token,
+                    )),
                     Some(value) => Ok(value),
-                };
+                }
             }
-            Expr::Assignment(name, expr) => match self.evaluate_expression(expr) {
-                Ok(value) => self
-                    .env
-                    .borrow_mut()
-                    .assign(String::from(name), value)
-                    .map(|_| Value::Nil),
-                Err(err) => {
-                    return Err(err);
+            Expr::Assignment(token, expr, local) => match self.evaluate_expression(expr) {
+                Ok(value) => {
+                    let name = token.symbol();
+                    let assigned = match local.get() {
+                        Some(local) => {
+                            self.env
+                                .borrow_mut()
+                                .assign_at(local.depth, local.slot, value.clone())
+                        }
+                        None => match self.globals.get_mut(&name) {
+                            Some(global) => {
+                                *global = value.clone();
+                                true
+                            }
+                            None => false,
+                        },
+                    };
+                    // evaluates to the assigned value, as setting a property does
+                    if assigned {
+                        Ok(value)
+                    } else {
+                        Err(RuntimeError::new(
+                            RuntimeErrorType::UndefinedVariable(name.to_string()),
+                            token,
+                        ))
+                    }
                 }
+                Err(err) => Err(err),
             },
-            Expr::Call(expr, args) => {
+            Expr::Call(expr, args, paren) => {
                 let callee = self.evaluate_expression(expr)?;
 
-                let mut evaluated_args = Vec::new();
+                // the callee and arguments so far stay rooted while later arguments are evaluated
+                let base = self.temporaries.len();
+                self.temporaries.push(callee);
                 for arg in args {
-                    let value = self.evaluate_expression(arg)?;
-                    evaluated_args.push(value);
+                    match self.evaluate_expression(arg) {
+                        Ok(value) => self.temporaries.push(value),
+                        Err(err) => {
+                            self.temporaries.truncate(base);
+                            return Err(err);
+                        }
+                    }
                 }
+                let mut evaluated_args = self.temporaries.split_off(base);
+                let callee = evaluated_args.remove(0);
 
-                match callee {
-                    Value::NativeFunction(fun) => (fun.callable)(evaluated_args.as_slice()),
-                    Value::Function {
-                        name: _,
-                        params,
-                        closure,
-                        body,
-                    } => {
-                        let mut env = Environment::new_with_enclosing(closure);
-                        for (i, arg) in params.iter().enumerate() {
-                            env.borrow_mut()
-                                .define(arg.clone(), evaluated_args[i].clone());
-                        }
-                        let mut interpreter = Interpreter { env };
-                        for statement in body {
-                            match interpreter.evaluate_statement(&statement) {
-                                Ok(_) => {}
-                                Err(err) => {
-                                    return match err {
-                                        RuntimeError::Return(value) => Ok(value),
-                                        _ => Err(err),
-                                    }
-                                }
-                            }
+                self.call(callee, evaluated_args, paren)
+            }
+            Expr::Get(object, name) => match self.evaluate_expression(object)? {
+                Value::Instance(instance) => get_property(&mut self.heap, &instance, name.symbol())
+                    .map_err(|error_type| RuntimeError::new(error_type, name)),
+                _ => Err(RuntimeError::new(
+                    RuntimeErrorType::Runtime {
+                        message: String::from("Only instances have properties"),
+                    },
+                    name,
+                )),
+            },
+            Expr::Set(object, name, value) => match self.evaluate_expression(object)? {
+                Value::Instance(instance) => {
+                    let (_, value) =
+                        self.evaluate_rooted(Value::Instance(instance.clone()), value)?;
+                    instance
+                        .borrow_mut()
+                        .fields
+                        .insert(name.symbol(), value.clone());
+                    Ok(value)
+                }
+                _ => Err(RuntimeError::new(
+                    RuntimeErrorType::Runtime {
+                        message: String::from("Only instances have fields"),
+                    },
+                    name,
+                )),
+            },
+            Expr::This(keyword, local) => match self.look_up_variable(Symbol::THIS, local.get()) {
+                None => Err(RuntimeError::new(
+                    RuntimeErrorType::Runtime {
+                        message: String::from("Can't use 'this' outside of a class"),
+                    },
+                    keyword,
+                )),
+                Some(value) => Ok(value),
+            },
+            Expr::Interpolation(parts) => {
+                let mut result = String::new();
+                for part in parts {
+                    let value = self.evaluate_expression(part)?;
+                    result.push_str(&value.to_string());
+                }
+                Ok(Value::String(Rc::from(result)))
+            }
+            Expr::Super(_, method, local) => {
+                // `this` is always bound in the scope just inside the one holding `super`, and
+                // both are the only name in their scope
+                let (superclass, this) = match local.get() {
+                    Some(local) if local.depth > 0 => (
+                        self.env.borrow().get_at(local.depth, 0),
+                        self.env.borrow().get_at(local.depth - 1, 0),
+                    ),
+                    _ => (None, None),
+                };
+                match (superclass, this) {
+                    (Some(Value::Class(superclass)), Some(Value::Instance(instance))) => {
+                        let name = method.symbol();
+                        match superclass.find_method(name) {
+                            Some(method) => Ok(bind(&mut self.heap, method, &instance)),
+                            None => Err(RuntimeError::new(
+                                RuntimeErrorType::Runtime {
+                                    message: format!("Undefined property '{}'", name),
+                                },
+                                method,
+                            )),
                         }
-                        Ok(Value::Nil)
                     }
-                    _ => return Err(RuntimeError::InvalidFunction),
+                    _ => Err(RuntimeError::new(
+                        RuntimeErrorType::Runtime {
+                            message: String::from("Can't use 'super' outside of a subclass"),
+                        },
+                        method,
+                    )),
+                }
+            }
+        }
+    }
+
+    fn look_up_variable(&self, name: Symbol, local: Option<Local>) -> Option<Value> {
+        match local {
+            Some(local) => self.env.borrow().get_at(local.depth, local.slot),
+            None => self.globals.get(&name).cloned(),
+        }
+    }
+
+    // declarations at the top level are globals, anywhere else they fill the next slot
+    fn define(&mut self, name: Symbol, value: Value) {
+        if Rc::ptr_eq(&self.env, &self.top_level) {
+            self.globals.insert(name, value);
+        } else {
+            self.env.borrow_mut().define(value);
+        }
+    }
+
+    // errors raised by the call itself point at the call's closing paren
+    fn call(
+        &mut self,
+        callee: Value,
+        evaluated_args: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::NativeFunction(fun) => check_arity(&fun.name, fun.arity, evaluated_args.len())
+                .and_then(|_| (fun.callable)(evaluated_args.as_slice()))
+                .map_err(|error_type| RuntimeError::new(error_type, paren)),
+            Value::Function {
+                declaration,
+                closure,
+                is_initializer,
+            } => {
+                let name = declaration.name.symbol();
+                check_arity(name, declaration.params.len(), evaluated_args.len())
+                    .map_err(|error_type| RuntimeError::new(error_type, paren))?;
+                if self.call_depth == MAX_CALL_DEPTH {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorType::Runtime {
+                            message: String::from("Stack overflow"),
+                        },
+                        paren,
+                    ));
+                }
+                let env = self.heap.environment(closure.clone());
+                for arg in evaluated_args {
+                    env.borrow_mut().define(arg);
+                }
+                self.call_depth += 1;
+                let flow = self.execute_block(&declaration.block, env);
+                self.call_depth -= 1;
+                let flow = flow.map_err(|err| err.called_from(name.as_str(), paren.line))?;
+                let result = match flow {
+                    ControlFlow::Return(value) => value,
+                    ControlFlow::Normal => Value::Nil,
+                };
+                if is_initializer {
+                    // init always hands back the instance it was bound to
+                    return Ok(closure.borrow().get_at(0, 0).unwrap());
+                }
+                Ok(result)
+            }
+            Value::Class(class) => {
+                let initializer = class.find_method(Symbol::INIT);
+                // a class takes the arguments of its init method, and is named in arity errors
+                let arity = match &initializer {
+                    Some(Value::Function { declaration, .. }) => declaration.params.len(),
+                    _ => 0,
+                };
+                check_arity(class.name, arity, evaluated_args.len())
+                    .map_err(|error_type| RuntimeError::new(error_type, paren))?;
+                let instance = self.heap.instance(class.clone());
+                if let Some(initializer) = initializer {
+                    let initializer = bind(&mut self.heap, initializer, &instance);
+                    self.call(initializer, evaluated_args, paren)?;
                 }
+                Ok(Value::Instance(instance))
             }
+            _ => Err(RuntimeError::new(RuntimeErrorType::InvalidFunction, paren)),
         }
     }
 
-    fn evaluate_statement(&mut self, statement: &Statement) -> Result<(), RuntimeError> {
+    fn evaluate_statement(&mut self, statement: &Statement) -> Result<ControlFlow, RuntimeError> {
         match statement {
             Statement::Expression(expr) => {
-                return match self.evaluate_expression(expr) {
-                    Ok(_value) => Ok(()),
-                    Err(err) => Err(err),
-                }
+                self.evaluate_expression(expr)?;
             }
-            Statement::Print(expr) => match self.evaluate_expression(expr) {
+            Statement::Print(_, expr) => match self.evaluate_expression(expr) {
                 Ok(value) => match value {
                     Value::Number(num) => println!("{}", num),
                     Value::String(str) => println!("{}", str),
@@ -245,39 +688,24 @@ impl Interpreter {
                     Value::NativeFunction(native_function) => {
                         println!("Function: {}", native_function.name)
                     }
-                    Value::Function { name, .. } => {
-                        println!("Function: {}", name)
+                    Value::Function { declaration, .. } => {
+                        println!("Function: {}", declaration.name.symbol())
                     }
+                    Value::Class(_) | Value::Instance(_) => println!("{}", value),
                 },
                 Err(err) => return Err(err),
             },
             Statement::Declaration(name, expr) => {
-                let name = String::from_utf8(name.lexeme.clone()).unwrap();
-                return match expr {
-                    None => {
-                        self.env.borrow_mut().define(name, Value::Nil);
-                        Ok(())
-                    }
-                    Some(expr) => match self.evaluate_expression(expr) {
-                        Ok(value) => {
-                            self.env.borrow_mut().define(name, value);
-                            Ok(())
-                        }
-                        Err(runtime_error) => Err(runtime_error),
-                    },
+                let name = name.symbol();
+                let value = match expr {
+                    None => Value::Nil,
+                    Some(expr) => self.evaluate_expression(expr)?,
                 };
+                self.define(name, value);
             }
             Statement::Block(statements) => {
-                let env = Environment::new_with_enclosing(self.env.clone());
-                self.env = env;
-                match self.evaluate(statements) {
-                    Ok(result) => {
-                        return Ok(result);
-                    }
-                    Err(err) => {
-                        return Err(err);
-                    }
-                };
+                let env = self.heap.environment(self.env.clone());
+                return self.execute_block(statements, env);
             }
             Statement::If {
                 condition,
@@ -286,40 +714,178 @@ impl Interpreter {
             } => {
                 let condition = self.evaluate_expression(condition)?;
 
-                if let Value::Bool(true) = condition {
+                if is_truthy(&condition) {
                     return self.evaluate_statement(then_branch);
                 } else if let Some(else_branch) = else_branch {
                     return self.evaluate_statement(else_branch);
                 }
             }
-            Statement::Function {
-                name,
-                params,
-                block,
-            } => {
-                let name = String::from_utf8(name.lexeme.clone()).unwrap();
+            Statement::While { condition, body } => {
+                while is_truthy(&self.evaluate_expression(condition)?) {
+                    self.safe_point();
+                    match self.evaluate_statement(body)? {
+                        ControlFlow::Normal => {}
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::Function(declaration) => {
                 let function = Value::Function {
-                    name: name.clone(),
-                    params: params
-                        .iter()
-                        .map(|p| String::from_utf8(p.lexeme.clone()).unwrap())
-                        .collect(),
+                    declaration: declaration.clone(),
                     closure: self.env.clone(),
-                    body: block.clone(),
+                    is_initializer: false,
                 };
-                self.env.borrow_mut().define(name, function);
+                self.define(declaration.name.symbol(), function);
             }
-            Statement::Return(_, return_value) => match return_value {
-                None => {
-                    return Err(RuntimeError::Return(Value::Nil));
-                }
-                Some(expr) => {
-                    let value = self.evaluate_expression(expr)?;
-                    return Err(RuntimeError::Return(value));
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    None => None,
+                    Some(expr) => match self.evaluate_expression(expr)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            let token = match expr {
+                                Expr::Variable(token, _) => token,
+                                _ => name,
+                            };
+                            return Err(RuntimeError::new(
+                                RuntimeErrorType::Runtime {
+                                    message: String::from("Superclass must be a class"),
+                                },
+                                token,
+                            ));
+                        }
+                    },
+                };
+                let name = name.symbol();
+
+                // methods of a subclass close over a scope where `super` is the superclass
+                let method_env = match &superclass {
+                    None => self.env.clone(),
+                    Some(superclass) => {
+                        let env = self.heap.environment(self.env.clone());
+                        env.borrow_mut().define(Value::Class(superclass.clone()));
+                        env
+                    }
+                };
+
+                let mut class_methods = HashMap::new();
+                for method in methods {
+                    if let Statement::Function(declaration) = method {
+                        let method_name = declaration.name.symbol();
+                        let function = Value::Function {
+                            declaration: declaration.clone(),
+                            closure: method_env.clone(),
+                            is_initializer: method_name == Symbol::INIT,
+                        };
+                        class_methods.insert(method_name, function);
+                    }
                 }
+                let class = Value::Class(Rc::new(Class {
+                    name,
+                    superclass,
+                    methods: class_methods,
+                }));
+                self.define(name, class);
+            }
+            Statement::Return(_, return_value) => {
+                let value = match return_value {
+                    None => Value::Nil,
+                    Some(expr) => self.evaluate_expression(expr)?,
+                };
+                return Ok(ControlFlow::Return(value));
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+}
+
+// nil and false are falsey, everything else is truthy
+fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Bool(false))
+}
+
+// functions, classes and instances are only equal to themselves
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Nil, Value::Nil) => true,
+        (Value::NativeFunction(a), Value::NativeFunction(b)) => a == b,
+        (
+            Value::Function {
+                declaration: a_declaration,
+                closure: a_closure,
+                ..
             },
+            Value::Function {
+                declaration: b_declaration,
+                closure: b_closure,
+                ..
+            },
+        ) => Rc::ptr_eq(a_declaration, b_declaration) && Rc::ptr_eq(a_closure, b_closure),
+        (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+        (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+fn get_property(
+    heap: &mut Heap,
+    instance: &Rc<RefCell<Instance>>,
+    name: Symbol,
+) -> Result<Value, RuntimeErrorType> {
+    if let Some(value) = instance.borrow().fields.get(&name) {
+        return Ok(value.clone());
+    }
+    let method = instance.borrow().class.find_method(name);
+    match method {
+        Some(method) => Ok(bind(heap, method, instance)),
+        None => Err(RuntimeErrorType::Runtime {
+            message: format!("Undefined property '{}'", name),
+        }),
+    }
+}
+
+// wraps a method's closure in a new scope with `this` bound to the given instance
+fn bind(heap: &mut Heap, method: Value, instance: &Rc<RefCell<Instance>>) -> Value {
+    match method {
+        Value::Function {
+            declaration,
+            closure,
+            is_initializer,
+        } => {
+            let env = heap.environment(closure);
+            env.borrow_mut().define(Value::Instance(instance.clone()));
+            Value::Function {
+                declaration,
+                closure: env,
+                is_initializer,
+            }
         }
+        _ => method,
+    }
+}
+
+// A function is called with exactly as many arguments as it has parameters. The name is only
+// turned into a string for the error, since looking up a symbol's name locks the interner.
+pub fn check_arity(
+    function: impl Display,
+    expected: usize,
+    got: usize,
+) -> Result<(), RuntimeErrorType> {
+    if expected == got {
         Ok(())
+    } else {
+        Err(RuntimeErrorType::ArityMismatch {
+            function: function.to_string(),
+            expected,
+            got,
+        })
     }
 }
 
@@ -327,20 +893,45 @@ impl Interpreter {
 mod tests {
     use super::*;
     use crate::parser::parse;
+    use crate::resolver::resolve;
     use crate::scanner;
+    use crate::test_support::allocations;
 
     #[test]
     fn test_interpreter_assignment() {
         let input = "
         var a = 4;
         print a;";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
         let mut interpreter = Interpreter::new();
         let result = interpreter.evaluate(&statements);
         assert_eq!(result, Ok(()));
     }
 
+    #[test]
+    fn test_chained_assignment() {
+        let input = "
+        var a;
+        var b;
+        a = b = 1;
+        { var c; var d; c = d = 2; a = a + c + d; }";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.evaluate(&statements), Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("a")).cloned(),
+            Some(Value::Number(5.0))
+        );
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("b")).cloned(),
+            Some(Value::Number(1.0))
+        );
+    }
+
     #[test]
     fn test_blocks() {
         let input = "
@@ -350,8 +941,9 @@ mod tests {
             print a;
         }
         print a;";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
         let mut interpreter = Interpreter::new();
         let result = interpreter.evaluate(&statements);
         assert_eq!(result, Ok(()));
@@ -365,8 +957,9 @@ mod tests {
             print a;
         }
         ";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
         let mut interpreter = Interpreter::new();
         let result = interpreter.evaluate(&statements);
         assert_eq!(result, Ok(()));
@@ -377,8 +970,9 @@ mod tests {
         let input = "
         clock();
         ";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
         let mut interpreter = Interpreter::new();
         let result = interpreter.evaluate(&statements);
         assert_eq!(result, Ok(()));
@@ -401,11 +995,890 @@ var counter = makeCounter();
 counter();
 
         ";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
         let mut interpreter = Interpreter::new();
         let result = interpreter.evaluate(&statements);
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_nested_loops() {
+        let input = "
+        var total = 0;
+        for (var i = 0; i < 3; i = i + 1) {
+            var j = 0;
+            while (j < 4) {
+                total = total + 1;
+                j = j + 1;
+            }
+        }
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("total")).cloned(),
+            Some(Value::Number(12.0))
+        );
+    }
+
+    #[test]
+    fn test_closure_over_loop_variable() {
+        let input = "
+        var first;
+        var last;
+        for (var i = 0; i < 3; i = i + 1) {
+            var captured = i;
+            fun get() {
+                return captured;
+            }
+            fun getLoopVariable() {
+                return i;
+            }
+            if (i == 0) {
+                first = get;
+            }
+            last = getLoopVariable;
+        }
+        var firstResult = first();
+        var lastResult = last();
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        // each iteration of the body gets its own scope, while the loop variable is shared
+        assert_eq!(
+            interpreter
+                .globals
+                .get(&Symbol::intern("firstResult"))
+                .cloned(),
+            Some(Value::Number(0.0))
+        );
+        assert_eq!(
+            interpreter
+                .globals
+                .get(&Symbol::intern("lastResult"))
+                .cloned(),
+            Some(Value::Number(3.0))
+        );
+    }
+
+    #[test]
+    fn test_class_with_initializer_and_methods() {
+        let input = "
+        class Counter {
+            init(start) {
+                this.count = start;
+            }
+            increment() {
+                this.count = this.count + 1;
+                return this;
+            }
+        }
+        var counter = Counter(10);
+        counter.increment().increment();
+        var bound = counter.increment;
+        bound();
+        var count = counter.count;
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("count")).cloned(),
+            Some(Value::Number(13.0))
+        );
+    }
+
+    #[test]
+    fn test_initializer_returns_instance() {
+        let input = "
+        class Point {
+            init() {
+                this.x = 1;
+                return;
+            }
+        }
+        var point = Point();
+        var again = point.init();
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert!(matches!(
+            interpreter.globals.get(&Symbol::intern("again")).cloned(),
+            Some(Value::Instance(_))
+        ));
+    }
+
+    #[test]
+    fn test_undefined_property() {
+        let input = "
+        class Empty {}
+        var empty = Empty();
+        empty.missing;
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Undefined property 'missing'")
+            })
+        );
+    }
+
+    #[test]
+    fn test_property_on_non_instance() {
+        let input = "
+        var a = 1;
+        a.b = 2;
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Only instances have fields")
+            })
+        );
+    }
+
+    #[test]
+    fn test_inherited_and_super_methods() {
+        let input = "
+        class Shape {
+            init(size) {
+                this.size = size;
+            }
+            area() {
+                return this.size;
+            }
+            double() {
+                return this.area() * 2;
+            }
+        }
+        class Square < Shape {
+            area() {
+                return super.area() * this.size;
+            }
+        }
+        var square = Square(3);
+        var area = square.area();
+        var doubled = square.double();
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("area")).cloned(),
+            Some(Value::Number(9.0))
+        );
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("doubled")).cloned(),
+            Some(Value::Number(18.0))
+        );
+    }
+
+    #[test]
+    fn test_inherit_from_non_class() {
+        let input = "
+        var NotAClass = 1;
+        class Sub < NotAClass {}
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Superclass must be a class")
+            })
+        );
+    }
+
+    #[test]
+    fn test_super_outside_subclass() {
+        let input = "
+        class Base {
+            method() {
+                return super.method();
+            }
+        }
+        Base().method();
+        ";
+        // the resolver rejects this, so run the unresolved statements to reach the runtime check
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Can't use 'super' outside of a subclass")
+            })
+        );
+    }
+
+    #[test]
+    fn test_logical_operators_return_deciding_operand() {
+        let input = "
+        var a = nil or 2;
+        var b = 1 and 3;
+        var c = false and 4;
+        var d = nil and 5;
+        var e = 6 or 7;
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        let globals = &interpreter.globals;
+        assert_eq!(
+            globals.get(&Symbol::intern("a")).cloned(),
+            Some(Value::Number(2.0))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("b")).cloned(),
+            Some(Value::Number(3.0))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("c")).cloned(),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(globals.get(&Symbol::intern("d")).cloned(), Some(Value::Nil));
+        assert_eq!(
+            globals.get(&Symbol::intern("e")).cloned(),
+            Some(Value::Number(6.0))
+        );
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        let input = "
+        var calls = 0;
+        fun touch() {
+            calls = calls + 1;
+            return true;
+        }
+        var instance = nil;
+        var guarded = instance and instance.missing;
+        false and touch();
+        true or touch();
+        true and touch();
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("guarded")).cloned(),
+            Some(Value::Nil)
+        );
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("calls")).cloned(),
+            Some(Value::Number(1.0))
+        );
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        let input = "
+        var greeting = \"hello\" + \" \" + \"world\";
+        var count = \"count: \" + 3;
+        var total = 1.5 + \" total\";
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        let globals = &interpreter.globals;
+        assert_eq!(
+            globals.get(&Symbol::intern("greeting")).cloned(),
+            Some(Value::String(Rc::from("hello world")))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("count")).cloned(),
+            Some(Value::String(Rc::from("count: 3")))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("total")).cloned(),
+            Some(Value::String(Rc::from("1.5 total")))
+        );
+    }
+
+    #[test]
+    fn test_equality_and_comparison() {
+        let input = "
+        class Point {}
+        var point = Point();
+        fun f() {}
+        var results = 0;
+        if (\"a\" == \"a\") results = results + 1;
+        if (\"a\" != \"b\") results = results + 1;
+        if (nil == nil) results = results + 1;
+        if (1 != \"1\") results = results + 1;
+        if (true != nil) results = results + 1;
+        if (point == point) results = results + 1;
+        if (Point() != point) results = results + 1;
+        if (f == f) results = results + 1;
+        if (\"apple\" < \"banana\") results = results + 1;
+        if (\"b\" >= \"abc\") results = results + 1;
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("results")).cloned(),
+            Some(Value::Number(10.0))
+        );
+    }
+
+    #[test]
+    fn test_invalid_string_operation() {
+        let input = "
+        \"a\" - 1;
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Invalid operation: a - 1")
+            })
+        );
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let input = "
+        var a = 1;
+        var b = 2.5;
+        class Point {}
+        var report = \"total: ${a + b}, ${nil} ${a < b} ${Point()} ${\"in ${\"ner\"}\"}\";
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("report")).cloned(),
+            Some(Value::String(Rc::from(
+                "total: 3.5, nil true Point instance in ner"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_unary_operators() {
+        let input = "
+        var flag = false;
+        var negated = -(1 + 2);
+        var doubleNegated = --4;
+        var notFlag = !flag;
+        var notNil = !nil;
+        var notZero = !0;
+        var notString = !!\"\";
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        let globals = &interpreter.globals;
+        assert_eq!(
+            globals.get(&Symbol::intern("negated")).cloned(),
+            Some(Value::Number(-3.0))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("doubleNegated")).cloned(),
+            Some(Value::Number(4.0))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("notFlag")).cloned(),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("notNil")).cloned(),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("notZero")).cloned(),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("notString")).cloned(),
+            Some(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_negate_non_number() {
+        let input = "
+        -\"abc\";
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::OperandMustBeNumber(
+                UnaryOperator::Minus,
+                String::from("abc")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_closure_ignores_later_shadowing() {
+        let input = "
+        var a = \"global\";
+        var results = \"\";
+        {
+            fun showA() {
+                results = results + a + \" \";
+            }
+            showA();
+            var a = \"block\";
+            showA();
+        }
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("results")).cloned(),
+            Some(Value::String(Rc::from("global global ")))
+        );
+    }
+
+    #[test]
+    fn test_return_unwinds_loops_and_blocks() {
+        let input = "
+        fun find(limit) {
+            var i = 0;
+            while (true) {
+                {
+                    if (i == limit) return i;
+                }
+                i = i + 1;
+            }
+        }
+        var found = find(3);
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&statements);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("found")).cloned(),
+            Some(Value::Number(3.0))
+        );
+    }
+
+    #[test]
+    fn test_return_is_not_a_runtime_error() {
+        let tokens = scanner::scan(String::from("return 1;")).unwrap();
+        let statements = parse(tokens).unwrap();
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter.evaluate_statement(&statements[0]),
+            Ok(ControlFlow::Return(Value::Number(1.0)))
+        );
+        assert_eq!(interpreter.evaluate(&statements), Ok(()));
+    }
+
+    #[test]
+    fn test_runtime_error_location_and_call_stack() {
+        let input = "fun inner() {
+    return \"a\" - 1;
+}
+fun outer() {
+    return inner();
+}
+outer();";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.evaluate(&statements).unwrap_err();
+
+        assert_eq!((error.line, error.column), (2, 16));
+        assert_eq!(
+            error.call_stack,
+            vec![
+                CallSite {
+                    function: String::from("inner"),
+                    line: 5
+                },
+                CallSite {
+                    function: String::from("outer"),
+                    line: 7
+                },
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid operation: a - 1
+    [line 2 column 16] in inner()
+    [line 5] in outer()
+    [line 7] in script"
+        );
+    }
+
+    #[test]
+    fn test_arity_is_checked() {
+        let arity_error = |input: &str| {
+            let tokens = scanner::scan(String::from(input)).unwrap();
+            let statements = parse(tokens).unwrap();
+            resolve(&statements).unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter
+                .evaluate(&statements)
+                .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(
+            arity_error("fun add(a, b) { return a + b; }\nadd(1);").unwrap_err(),
+            "add() expected 2 arguments but got 1\n    [line 2 column 6] in script"
+        );
+        assert_eq!(
+            arity_error("fun add(a, b) { return a + b; }\nadd(1, 2, 3);").unwrap_err(),
+            "add() expected 2 arguments but got 3\n    [line 2 column 12] in script"
+        );
+        assert_eq!(
+            arity_error("clock(1);").unwrap_err(),
+            "clock() expected 0 arguments but got 1\n    [line 1 column 8] in script"
+        );
+        assert_eq!(
+            arity_error("class A {}\nA(1);").unwrap_err(),
+            "A() expected 0 arguments but got 1\n    [line 2 column 4] in script"
+        );
+        assert_eq!(
+            arity_error("class A { init(a) {} }\nA();").unwrap_err(),
+            "A() expected 1 argument but got 0\n    [line 2 column 3] in script"
+        );
+    }
+
+    #[test]
+    fn test_undefined_variable_read() {
+        let input = "var count = 1;
+fun show() {
+    print cuont;
+}
+show();";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.evaluate(&statements).unwrap_err();
+
+        assert_eq!(
+            error.error_type,
+            RuntimeErrorType::UndefinedVariable(String::from("cuont"))
+        );
+        assert_eq!((error.line, error.column), (3, 11));
+        assert_eq!(error.call_stack.len(), 1);
+    }
+
+    #[test]
+    fn test_string_literals_are_shared() {
+        let input = "var s = \"constant\";";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate(&statements).unwrap();
+
+        let literal = match &statements[0] {
+            Statement::Declaration(_, Some(Expr::Literal(LiteralValue::String(literal), _))) => {
+                literal
+            }
+            _ => panic!("Expected string declaration"),
+        };
+        match interpreter.globals.get(&Symbol::intern("s")) {
+            Some(Value::String(value)) => assert!(Rc::ptr_eq(value, literal)),
+            _ => panic!("Expected string"),
+        }
+    }
+
+    #[test]
+    fn test_function_values_share_their_declaration() {
+        let input = "fun a() { return 1; } var b = a;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate(&statements).unwrap();
+
+        let declaration = match &statements[0] {
+            Statement::Function(declaration) => declaration,
+            _ => panic!("Expected function"),
+        };
+        for name in ["a", "b"].iter() {
+            match interpreter.globals.get(&Symbol::intern(name)) {
+                Some(Value::Function {
+                    declaration: shared,
+                    ..
+                }) => assert!(Rc::ptr_eq(shared, declaration)),
+                _ => panic!("Expected function"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_collects_inside_a_long_running_statement() {
+        let input = "
+        fun makeCounter() {
+            var i = 0;
+            fun count() {
+                i = i + 1;
+                return i;
+            }
+            return count;
+        }
+        fun churn(n) {
+            var i = 0;
+            while (i < n) {
+                makeCounter();
+                i = i + 1;
+            }
+            return n;
+        }
+        fun pair(counter, n) {
+            return counter() + n;
+        }
+        var result = pair(makeCounter(), churn(5000));
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+        // skips the collections between top level statements
+        for statement in &statements {
+            interpreter.evaluate_statement(statement).unwrap();
+        }
+
+        assert!(interpreter.live_objects() <= 2048);
+        // the counter passed to pair stayed reachable while churn collected
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("result")).cloned(),
+            Some(Value::Number(5001.0))
+        );
+    }
+
+    #[test]
+    fn test_repl_session_keeps_global_scope() {
+        let mut interpreter = Interpreter::new();
+        let mut run_line = |input: &str| {
+            let tokens = scanner::scan(String::from(input)).unwrap();
+            let statements = parse(tokens).unwrap();
+            resolve(&statements).unwrap();
+            let result = interpreter
+                .evaluate(&statements)
+                .map_err(|err| err.error_type);
+            // every line, however it ends, leaves the interpreter back in the global scope
+            assert!(
+                Rc::ptr_eq(&interpreter.env, &interpreter.top_level),
+                "{}",
+                input
+            );
+            result
+        };
+
+        assert_eq!(run_line("var a = 1;\n{\n  var inner = a + 2;\n}"), Ok(()));
+        assert_eq!(run_line("var b = 2;"), Ok(()));
+        assert!(run_line("{ var c = 1; c - \"x\"; }").is_err());
+        assert_eq!(run_line("fun f() { { return 1; } }\nvar r = f();"), Ok(()));
+        assert!(run_line("fun g() { { var d = nil; return d - 1; } }\ng();").is_err());
+        assert_eq!(
+            run_line("print c;"),
+            Err(RuntimeErrorType::UndefinedVariable(String::from("c")))
+        );
+
+        let globals = &interpreter.globals;
+        assert_eq!(
+            globals.get(&Symbol::intern("a")).cloned(),
+            Some(Value::Number(1.0))
+        );
+        assert_eq!(
+            globals.get(&Symbol::intern("b")).cloned(),
+            Some(Value::Number(2.0))
+        );
+        assert_eq!(globals.get(&Symbol::intern("inner")).cloned(), None);
+        assert_eq!(
+            globals.get(&Symbol::intern("r")).cloned(),
+            Some(Value::Number(1.0))
+        );
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        // the same stack programs get from main, which test threads don't have by default
+        let result = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let input = "
+                fun f(n) { if (n == 0) return 0; return f(n - 1) + 1; }
+                var deep = f(1000);
+                f(1024);";
+                let tokens = scanner::scan(String::from(input)).unwrap();
+                let statements = parse(tokens).unwrap();
+                resolve(&statements).unwrap();
+                let mut interpreter = Interpreter::new();
+                let result = interpreter.evaluate(&statements);
+                assert_eq!(
+                    interpreter.globals.get(&Symbol::intern("deep")).cloned(),
+                    Some(Value::Number(1000.0))
+                );
+                result.map_err(|err| err.error_type)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(
+            result,
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Stack overflow")
+            })
+        );
+    }
+
+    #[test]
+    fn test_recursive_fib_local_reads_do_not_allocate() {
+        // the same recursion, but the second version reads its local four more times per call
+        let fib_allocations = |input: &str| {
+            let tokens = scanner::scan(String::from(input)).unwrap();
+            let statements = parse(tokens).unwrap();
+            resolve(&statements).unwrap();
+            let mut interpreter = Interpreter::new();
+
+            let before = allocations();
+            interpreter.evaluate(&statements).unwrap();
+            let made = allocations() - before;
+            assert_eq!(
+                interpreter.globals.get(&Symbol::intern("result")).cloned(),
+                Some(Value::Number(6765.0))
+            );
+            made
+        };
+
+        assert_eq!(
+            fib_allocations(
+                "
+                fun fib(n) {
+                    if (n < 2) return n;
+                    return fib(n - 1) + fib(n - 2);
+                }
+                var result = fib(20);"
+            ),
+            fib_allocations(
+                "
+                fun fib(n) {
+                    if (n < 2) return n;
+                    n; n; n; n;
+                    return fib(n - 1) + fib(n - 2);
+                }
+                var result = fib(20);"
+            )
+        );
+    }
+
+    #[test]
+    fn test_call_loop_allocations() {
+        let input = "
+        fun add(a, b) {
+            var sum = a + b;
+            return sum;
+        }
+        var total = 0;
+        for (var i = 0; i < 10000; i = i + 1) {
+            total = add(total, i);
+        }
+        ";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let mut interpreter = Interpreter::new();
+
+        let before = allocations();
+        interpreter.evaluate(&statements).unwrap();
+        let made = allocations() - before;
+        // each call currently makes about five allocations
+        assert!(made < 6 * 10_000, "10000 calls made {} allocations", made);
+
+        assert_eq!(
+            interpreter.globals.get(&Symbol::intern("total")).cloned(),
+            Some(Value::Number(49995000.0))
+        );
+    }
 }
@@ -1,33 +1,110 @@
+mod chunk;
+mod compiler;
+mod diagnostics;
+mod disassembler;
 mod environment;
+mod gc;
 mod interpreter;
 mod parser;
+mod resolver;
 mod scanner;
+mod symbol;
+#[cfg(test)]
+mod test_support;
+mod vm;
 
+use crate::diagnostics::Diagnostic;
 use crate::interpreter::Interpreter;
+use crate::parser::Statement;
+use crate::vm::Vm;
 use std::io::Write;
-use std::{env, io};
+use std::{env, io, thread};
+
+// Programs run on the tree walking interpreter by default, or on the bytecode vm with --vm
+enum Backend {
+    TreeWalk(Interpreter),
+    Bytecode(Vm),
+}
+
+impl Backend {
+    fn new(use_vm: bool) -> Self {
+        if use_vm {
+            Backend::Bytecode(Vm::new())
+        } else {
+            Backend::TreeWalk(Interpreter::new())
+        }
+    }
+
+    fn execute(&mut self, source: &str, statements: &[Statement]) {
+        let result = match self {
+            Backend::TreeWalk(interpretter) => interpretter.evaluate(statements),
+            Backend::Bytecode(vm) => match compiler::compile(statements) {
+                Ok(function) => vm.interpret(function),
+                Err(compile_error) => {
+                    report(source, &[Diagnostic::from(&compile_error)]);
+                    return;
+                }
+            },
+        };
+        match result {
+            Err(runtime_err) => report(source, &[Diagnostic::from(&runtime_err)]),
+            _ => {
+                println!()
+            }
+        }
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let filepath = args.get(1);
+    // deep recursion in the tree walking interpreter needs more than the main thread's stack
+    thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(run_args)
+        .expect("Failed to start the interpreter thread")
+        .join()
+        .unwrap();
+}
 
-    if filepath.is_none() {
-        repl();
-    } else {
-        run_file(filepath.unwrap());
+fn run_args() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("disasm") {
+        match args.get(1) {
+            None => println!("Usage: cj-compiler disasm <file>"),
+            Some(filepath) => disassemble_file(filepath),
+        }
+        return;
+    }
+
+    let use_vm = args.iter().any(|arg| arg == "--vm");
+    let filepath = args.iter().find(|arg| *arg != "--vm");
+
+    match filepath {
+        None => repl(use_vm),
+        Some(filepath) => run_file(filepath, use_vm),
     }
 }
 
-fn run_file(filepath: &String) {
+fn run_file(filepath: &str, use_vm: bool) {
     let contents =
         std::fs::read_to_string(filepath).expect("Something went wrong reading the file");
-    let mut interpretter = Interpreter::new();
+    let mut backend = Backend::new(use_vm);
 
-    run(contents, &mut interpretter);
+    run(contents, &mut backend);
 }
 
-fn repl() {
-    let mut interpretter = Interpreter::new();
+fn disassemble_file(filepath: &str) {
+    let contents =
+        std::fs::read_to_string(filepath).expect("Something went wrong reading the file");
+    if let Some(statements) = parse_and_resolve(&contents) {
+        match compiler::compile(&statements) {
+            Ok(function) => print!("{}", disassembler::disassemble(&function)),
+            Err(compile_error) => report(&contents, &[Diagnostic::from(&compile_error)]),
+        }
+    }
+}
+
+fn repl(use_vm: bool) {
+    let mut backend = Backend::new(use_vm);
 
     loop {
         print!("> ");
@@ -36,33 +113,58 @@ fn repl() {
         io::stdin()
             .read_line(&mut buffer)
             .expect("Failed to read line");
-        run(buffer, &mut interpretter)
+        run(buffer, &mut backend)
     }
 }
 
-fn run(buffer: String, interpretter: &mut Interpreter) {
-    let tokens = scanner::scan(buffer);
+fn run(buffer: String, backend: &mut Backend) {
+    if let Some(statements) = parse_and_resolve(&buffer) {
+        backend.execute(&buffer, &statements);
+    }
+}
+
+// prints any scan, parse or resolve errors and returns None if there were some
+fn parse_and_resolve(source: &str) -> Option<Vec<Statement>> {
+    let tokens = match scanner::scan(String::from(source)) {
+        Ok(tokens) => tokens,
+        Err(scan_errors) => {
+            report(
+                source,
+                &scan_errors.iter().map(Diagnostic::from).collect::<Vec<_>>(),
+            );
+            return None;
+        }
+    };
 
     match parser::parse(tokens) {
-        Ok(statements) => match interpretter.evaluate(&statements) {
-            Err(runtime_err) => {
-                println!("runtime error {}", runtime_err)
-            }
-            _ => {
-                println!()
-            }
-        },
-        Err(parse_errors) => {
-            for parse_error in parse_errors {
-                let formatted_lexeme = String::from_utf8(parse_error.token.lexeme.clone()).unwrap();
-                println!(
-                    "{:?}: {:?} Line {:} column {:}",
-                    parse_error.error_type,
-                    formatted_lexeme,
-                    parse_error.token.line,
-                    parse_error.token.column
+        Ok(statements) => {
+            if let Err(resolve_errors) = resolver::resolve(&statements) {
+                report(
+                    source,
+                    &resolve_errors
+                        .iter()
+                        .map(Diagnostic::from)
+                        .collect::<Vec<_>>(),
                 );
+                return None;
             }
+            Some(statements)
         }
+        Err(parse_errors) => {
+            report(
+                source,
+                &parse_errors
+                    .iter()
+                    .map(Diagnostic::from)
+                    .collect::<Vec<_>>(),
+            );
+            None
+        }
+    }
+}
+
+fn report(source: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        println!("{}", diagnostic.render(source));
     }
 }
@@ -1,20 +1,42 @@
 use crate::scanner::{Literal, Token, TokenType};
+use std::cell::Cell;
 use std::fmt::{Display, Formatter};
-
+use std::rc::Rc;
+
+// Variables, assignments, `this` and `super` carry where the resolver found their declaration.
+// None means the name is looked up as a global. The resolver fills these in through a Cell, as
+// function bodies are shared behind an Rc.
+// Binary and unary expressions keep their operator token, and calls their closing paren, so
+// runtime errors can point at them. Literals keep their own token, and print statements their
+// keyword, so the compiler has a line for every instruction.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
-    Binary(Box<Expr>, Box<Expr>, BinaryOperator),
-    Unary(Box<Expr>, UnaryOperator),
-    Literal(LiteralValue),
-    Variable(Token),
-    Assignment(String, Box<Expr>),
-    Call(Box<Expr>, Vec<Expr>),
+    Binary(Box<Expr>, Box<Expr>, BinaryOperator, Token),
+    Logical(Box<Expr>, Box<Expr>, LogicalOperator),
+    Unary(Box<Expr>, UnaryOperator, Token),
+    Literal(LiteralValue, Token),
+    Variable(Token, Cell<Option<Local>>),
+    Assignment(Token, Box<Expr>, Cell<Option<Local>>),
+    Call(Box<Expr>, Vec<Expr>, Token),
+    Get(Box<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    This(Token, Cell<Option<Local>>),
+    Super(Token, Token, Cell<Option<Local>>),
+    Interpolation(Vec<Expr>),
+}
+
+// A local variable is found by going `depth` scopes out from where it is used, and is the
+// `slot`th name declared in that scope
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Local {
+    pub depth: usize,
+    pub slot: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Expression(Expr),
-    Print(Expr),
+    Print(Token, Expr),
     Declaration(Token, Option<Expr>),
     Block(Vec<Statement>),
     If {
@@ -22,12 +44,26 @@ pub enum Statement {
         then_branch: Box<Statement>,
         else_branch: Option<Box<Statement>>,
     },
-    Function {
-        name: Token,
-        params: Vec<Token>,
-        block: Vec<Statement>,
+    While {
+        condition: Expr,
+        body: Box<Statement>,
     },
+    Function(Rc<FunctionDecl>),
     Return(Token, Option<Expr>),
+    Class {
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Statement>,
+    },
+}
+
+// Shared by every function value made from the declaration, so copying or calling a function
+// doesn't copy its body
+#[derive(Debug, PartialEq)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub block: Vec<Statement>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -42,8 +78,6 @@ pub enum BinaryOperator {
     GreaterEqual,
     Less,
     LessEqual,
-    And,
-    Or,
 }
 
 impl Display for BinaryOperator {
@@ -59,22 +93,36 @@ impl Display for BinaryOperator {
             BinaryOperator::GreaterEqual => write!(f, ">="),
             BinaryOperator::Less => write!(f, "<"),
             BinaryOperator::LessEqual => write!(f, "<="),
-            BinaryOperator::And => write!(f, "and"),
-            BinaryOperator::Or => write!(f, "or"),
         }
     }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LogicalOperator {
+    And,
+    Or,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum UnaryOperator {
     Bang,
     Minus,
 }
 
+impl Display for UnaryOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOperator::Bang => write!(f, "!"),
+            UnaryOperator::Minus => write!(f, "-"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
     Number(f64),
-    String(String),
+    // shared, so evaluating the literal doesn't copy it
+    String(Rc<str>),
     Boolean(bool),
     Nil,
 }
@@ -83,13 +131,19 @@ pub enum LiteralValue {
 pub enum ErrorType {
     InvalidBinaryOperator,
     InvalidUnaryOperator,
-    UnexpectedCharacter,
+    // the tokens that would have been accepted, and a description of what was expected there
+    ExpectedToken {
+        expected: Vec<TokenType>,
+        context: &'static str,
+    },
+    ExpectedExpression,
     InvalidAssignmentTarget,
 }
 
 struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -98,42 +152,76 @@ pub struct ParseError {
     pub token: Token,
 }
 
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lexeme = String::from_utf8_lossy(&self.token.lexeme);
+        let found = match self.token.token_type {
+            TokenType::EOF => String::from("end of input"),
+            _ => format!("'{}'", lexeme),
+        };
+        match &self.error_type {
+            ErrorType::InvalidBinaryOperator => write!(f, "invalid binary operator '{}'", lexeme),
+            ErrorType::InvalidUnaryOperator => write!(f, "invalid unary operator '{}'", lexeme),
+            ErrorType::ExpectedToken { context, .. } => {
+                write!(f, "expected {}, found {}", context, found)
+            }
+            ErrorType::ExpectedExpression => write!(f, "expected expression, found {}", found),
+            ErrorType::InvalidAssignmentTarget => write!(f, "invalid assignment target"),
+        }
+    }
+}
+
 pub fn parse(tokens: Vec<Token>) -> Result<Vec<Statement>, Vec<ParseError>> {
     let mut parser = Parser::new(tokens);
 
     parser.parse()
 }
 
-fn lexeme_to_name(var_token: &Token) -> String {
-    String::from_utf8(var_token.lexeme.clone()).unwrap()
-}
-
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            errors: vec![],
+        }
     }
     fn parse(&mut self) -> Result<Vec<Statement>, Vec<ParseError>> {
         let mut statements: Vec<Statement> = vec![];
-        let mut errors: Vec<ParseError> = vec![];
 
         while !self.is_at_end() {
-            match self.declaration() {
-                Ok(statement) => {
-                    statements.push(statement);
-                }
-                Err(err) => {
-                    errors.push(err);
-                }
+            if let Some(statement) = self.recovering_declaration() {
+                statements.push(statement);
             }
         }
-        if errors.is_empty() {
+        if self.errors.is_empty() {
             Ok(statements)
         } else {
-            Err(errors)
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // records any error and skips to the start of the next statement, so that parsing can carry on
+    // and report later errors too
+    fn recovering_declaration(&mut self) -> Option<Statement> {
+        let start = self.current;
+        match self.declaration() {
+            Ok(statement) => Some(statement),
+            Err(err) => {
+                self.errors.push(err);
+                // always move past at least one token so a bad token can't be parsed forever
+                if self.current == start {
+                    self.advance();
+                }
+                self.synchronize();
+                None
+            }
         }
     }
 
     fn declaration(&mut self) -> Result<Statement, ParseError> {
+        if self.match_token(&[TokenType::Class]) {
+            return self.class_declaration();
+        }
         if self.match_token(&[TokenType::Fun]) {
             return self.function_declaration();
         }
@@ -143,14 +231,38 @@ impl Parser {
         self.statement()
     }
 
+    fn class_declaration(&mut self) -> Result<Statement, ParseError> {
+        let name = self.consume(TokenType::Identifier, "class name")?;
+
+        let mut superclass: Option<Expr> = None;
+        if self.match_token(&[TokenType::Less]) {
+            let superclass_name = self.consume(TokenType::Identifier, "superclass name")?;
+            superclass = Some(Expr::Variable(superclass_name, Cell::new(None)));
+        }
+
+        self.consume(TokenType::LeftBrace, "'{' before class body")?;
+
+        let mut methods: Vec<Statement> = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function_declaration()?);
+        }
+        self.consume(TokenType::RightBrace, "'}' after class body")?;
+
+        Ok(Statement::Class {
+            name,
+            superclass,
+            methods,
+        })
+    }
+
     fn function_declaration(&mut self) -> Result<Statement, ParseError> {
-        let name_token = self.consume(TokenType::Identifier)?;
+        let name_token = self.consume(TokenType::Identifier, "function name")?;
 
-        self.consume(TokenType::LeftParen)?;
+        self.consume(TokenType::LeftParen, "'(' after function name")?;
         let mut params: Vec<Token> = vec![];
         if !self.check(&TokenType::RightParen) {
             loop {
-                match self.consume(TokenType::Identifier) {
+                match self.consume(TokenType::Identifier, "parameter name") {
                     Ok(param) => {
                         params.push(param);
                     }
@@ -161,46 +273,50 @@ impl Parser {
                 }
             }
         }
-        self.consume(TokenType::RightParen)?;
-        self.consume(TokenType::LeftBrace)?;
+        self.consume(TokenType::RightParen, "')' after parameters")?;
+        self.consume(TokenType::LeftBrace, "'{' before function body")?;
         let mut statements: Vec<Statement> = vec![];
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            match self.declaration() {
-                Ok(statement) => {
-                    statements.push(statement);
-                }
-                Err(err) => return Err(err),
+            if let Some(statement) = self.recovering_declaration() {
+                statements.push(statement);
             }
         }
-        self.consume(TokenType::RightBrace)?;
-        Ok(Statement::Function {
+        self.consume(TokenType::RightBrace, "'}' after function body")?;
+        Ok(Statement::Function(Rc::new(FunctionDecl {
             name: name_token,
             params,
             block: statements,
-        })
+        })))
     }
 
     fn declaration_statement(&mut self) -> Result<Statement, ParseError> {
-        self.consume(TokenType::Identifier).and_then(|token| {
-            let mut initialiser: Option<Expr> = None;
-            if self.match_token(&[TokenType::Equal]) {
-                match self.expression() {
-                    Ok(expr) => {
-                        initialiser = Some(expr);
+        self.consume(TokenType::Identifier, "variable name")
+            .and_then(|token| {
+                let mut initialiser: Option<Expr> = None;
+                if self.match_token(&[TokenType::Equal]) {
+                    match self.expression() {
+                        Ok(expr) => {
+                            initialiser = Some(expr);
+                        }
+                        Err(err) => return Err(err),
                     }
-                    Err(err) => return Err(err),
                 }
-            }
 
-            self.consume(TokenType::Semicolon)
-                .map(|_| Statement::Declaration(token, initialiser))
-        })
+                self.consume(TokenType::Semicolon, "';' after variable declaration")
+                    .map(|_| Statement::Declaration(token, initialiser))
+            })
     }
 
     fn statement(&mut self) -> Result<Statement, ParseError> {
         if self.match_token(&[TokenType::If]) {
             return self.if_statement();
         }
+        if self.match_token(&[TokenType::While]) {
+            return self.while_statement();
+        }
+        if self.match_token(&[TokenType::For]) {
+            return self.for_statement();
+        }
         if self.match_token(&[TokenType::Print]) {
             return self.print_statement();
         }
@@ -210,7 +326,7 @@ impl Parser {
         if self.match_token(&[TokenType::LeftBrace]) {
             return self.block_statement();
         }
-        return self.expr_statement();
+        self.expr_statement()
     }
 
     fn return_statement(&mut self) -> Result<Statement, ParseError> {
@@ -224,63 +340,113 @@ impl Parser {
                 Err(err) => return Err(err),
             }
         }
-        self.consume(TokenType::Semicolon)?;
+        self.consume(TokenType::Semicolon, "';' after return value")?;
         Ok(Statement::Return(keyword, value))
     }
 
     fn if_statement(&mut self) -> Result<Statement, ParseError> {
-        self.consume(TokenType::LeftParen)
+        self.consume(TokenType::LeftParen, "'(' after 'if'")
             .and_then(|_| self.expression())
-            .and_then(|condition| match self.consume(TokenType::RightParen) {
-                Ok(_) => {
-                    let then_branch = self.statement();
-                    if then_branch.is_err() {
-                        return Err(then_branch.err().unwrap());
-                    }
-                    let mut else_branch: Option<Statement> = None;
-                    if self.match_token(&[TokenType::Else]) {
-                        else_branch = Some(self.statement()?);
+            .and_then(|condition| {
+                match self.consume(TokenType::RightParen, "')' after if condition") {
+                    Ok(_) => {
+                        let then_branch = self.statement();
+                        if then_branch.is_err() {
+                            return Err(then_branch.err().unwrap());
+                        }
+                        let mut else_branch: Option<Statement> = None;
+                        if self.match_token(&[TokenType::Else]) {
+                            else_branch = Some(self.statement()?);
+                        }
+                        Ok(Statement::If {
+                            condition,
+                            then_branch: Box::new(then_branch?),
+                            else_branch: else_branch.map(Box::new),
+                        })
                     }
-                    Ok(Statement::If {
-                        condition,
-                        then_branch: Box::new(then_branch?),
-                        else_branch: else_branch.map(Box::new),
-                    })
+                    Err(err) => Err(err),
                 }
-                Err(err) => return Err(err),
             })
     }
 
+    fn while_statement(&mut self) -> Result<Statement, ParseError> {
+        self.consume(TokenType::LeftParen, "'(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "')' after while condition")?;
+        let body = self.statement()?;
+        Ok(Statement::While {
+            condition,
+            body: Box::new(body),
+        })
+    }
+
+    // for loops are desugared into a while loop wrapped in a block, so the initialiser
+    // is scoped to the loop and the increment runs after each iteration of the body
+    fn for_statement(&mut self) -> Result<Statement, ParseError> {
+        self.consume(TokenType::LeftParen, "'(' after 'for'")?;
+
+        let initialiser = if self.match_token(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token(&[TokenType::Var]) {
+            Some(self.declaration_statement()?)
+        } else {
+            Some(self.expr_statement()?)
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            let semicolon = self.peek().expect("No token found").clone();
+            Expr::Literal(LiteralValue::Boolean(true), semicolon)
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "';' after loop condition")?;
+
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "')' after for clauses")?;
+
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            body = Statement::Block(vec![body, Statement::Expression(increment)]);
+        }
+        body = Statement::While {
+            condition,
+            body: Box::new(body),
+        };
+        if let Some(initialiser) = initialiser {
+            body = Statement::Block(vec![initialiser, body]);
+        }
+        Ok(body)
+    }
+
     fn block_statement(&mut self) -> Result<Statement, ParseError> {
         let mut statements: Vec<Statement> = vec![];
 
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            match self.declaration() {
-                Ok(next_declaration) => {
-                    statements.push(next_declaration);
-                }
-                Err(err) => return Err(err),
+            if let Some(next_declaration) = self.recovering_declaration() {
+                statements.push(next_declaration);
             }
         }
 
-        // there is a bug that is causing the following to fail - seems that the token has already been consumed
-        if let Err(err) = self.consume(TokenType::RightBrace) {
-            return Err(err);
-        }
+        self.consume(TokenType::RightBrace, "'}' after block")?;
 
         Ok(Statement::Block(statements))
     }
 
     fn print_statement(&mut self) -> Result<Statement, ParseError> {
+        let keyword = self.previous_token().clone();
         self.expression().and_then(|expr| {
-            self.consume(TokenType::Semicolon)
-                .map(|_| Statement::Print(expr))
+            self.consume(TokenType::Semicolon, "';' after value")
+                .map(|_| Statement::Print(keyword, expr))
         })
     }
 
     fn expr_statement(&mut self) -> Result<Statement, ParseError> {
         self.expression().and_then(|expr| {
-            self.consume(TokenType::Semicolon)
+            self.consume(TokenType::Semicolon, "';' after expression")
                 .map(|_| Statement::Expression(expr))
         })
     }
@@ -290,20 +456,28 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
-        return match self.equality() {
+        match self.logic_or() {
             Ok(equality_expr) => {
                 if self.match_token(&[TokenType::Equal]) {
                     let equals = self.previous_token().clone();
                     return match self.assignment() {
                         Ok(assignment_expr) => match equality_expr {
-                            Expr::Variable(var_token) => {
-                                let name = lexeme_to_name(&var_token);
-                                Ok(Expr::Assignment(name, Box::new(assignment_expr)))
+                            Expr::Variable(var_token, _) => Ok(Expr::Assignment(
+                                var_token,
+                                Box::new(assignment_expr),
+                                Cell::new(None),
+                            )),
+                            Expr::Get(object, name) => {
+                                Ok(Expr::Set(object, name, Box::new(assignment_expr)))
+                            }
+                            // the parser isn't confused, so report the error without synchronising
+                            _ => {
+                                self.errors.push(ParseError {
+                                    error_type: ErrorType::InvalidAssignmentTarget,
+                                    token: equals.clone(),
+                                });
+                                Ok(assignment_expr)
                             }
-                            _ => Err(ParseError {
-                                error_type: ErrorType::InvalidAssignmentTarget,
-                                token: equals.clone(),
-                            }),
                         },
                         Err(assignment_err) => Err(assignment_err),
                     };
@@ -311,7 +485,25 @@ impl Parser {
                 Ok(equality_expr)
             }
             Err(error) => Err(error),
-        };
+        }
+    }
+
+    fn logic_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.logic_and()?;
+        while self.match_token(&[TokenType::Or]) {
+            let right = self.logic_and()?;
+            expr = Expr::Logical(Box::new(expr), Box::new(right), LogicalOperator::Or);
+        }
+        Ok(expr)
+    }
+
+    fn logic_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.equality()?;
+        while self.match_token(&[TokenType::And]) {
+            let right = self.equality()?;
+            expr = Expr::Logical(Box::new(expr), Box::new(right), LogicalOperator::And);
+        }
+        Ok(expr)
     }
 
     fn equality(&mut self) -> Result<Expr, ParseError> {
@@ -319,11 +511,16 @@ impl Parser {
             Ok(left) => {
                 let mut expr = left;
                 while self.match_token(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-                    let operator_token = self.previous_token();
-                    match parse_binary_operator(operator_token) {
+                    let operator_token = self.previous_token().clone();
+                    match parse_binary_operator(&operator_token) {
                         Ok(binary_op) => match self.comparison() {
                             Ok(right) => {
-                                expr = Expr::Binary(Box::new(expr), Box::new(right), binary_op);
+                                expr = Expr::Binary(
+                                    Box::new(expr),
+                                    Box::new(right),
+                                    binary_op,
+                                    operator_token,
+                                );
                             }
                             Err(err_right) => return Err(err_right),
                         },
@@ -332,12 +529,12 @@ impl Parser {
                 }
                 Ok(expr)
             }
-            Err(left_err) => return Err(left_err),
+            Err(left_err) => Err(left_err),
         }
     }
 
     fn comparison(&mut self) -> Result<Expr, ParseError> {
-        return match self.term() {
+        match self.term() {
             Ok(left) => {
                 let mut expr = left;
                 while self.match_token(&[
@@ -346,12 +543,17 @@ impl Parser {
                     TokenType::Less,
                     TokenType::LessEqual,
                 ]) {
-                    let operator = self.previous_token();
-                    match parse_binary_operator(operator) {
+                    let operator = self.previous_token().clone();
+                    match parse_binary_operator(&operator) {
                         Ok(binary_op) => {
                             match self.term() {
                                 Ok(right) => {
-                                    expr = Expr::Binary(Box::new(expr), Box::new(right), binary_op);
+                                    expr = Expr::Binary(
+                                        Box::new(expr),
+                                        Box::new(right),
+                                        binary_op,
+                                        operator,
+                                    );
                                 }
                                 Err(right_err) => return Err(right_err),
                             };
@@ -362,20 +564,25 @@ impl Parser {
                 Ok(expr)
             }
             Err(left_err) => Err(left_err),
-        };
+        }
     }
 
     fn term(&mut self) -> Result<Expr, ParseError> {
-        return match self.factor() {
+        match self.factor() {
             Ok(left) => {
                 let mut expr = left;
                 while self.match_token(&[TokenType::Minus, TokenType::Plus]) {
-                    let operator = self.previous_token();
-                    match parse_binary_operator(operator) {
+                    let operator = self.previous_token().clone();
+                    match parse_binary_operator(&operator) {
                         Ok(binary_op) => {
                             match self.factor() {
                                 Ok(right) => {
-                                    expr = Expr::Binary(Box::new(expr), Box::new(right), binary_op);
+                                    expr = Expr::Binary(
+                                        Box::new(expr),
+                                        Box::new(right),
+                                        binary_op,
+                                        operator,
+                                    );
                                 }
                                 Err(right_err) => return Err(right_err),
                             };
@@ -386,20 +593,25 @@ impl Parser {
                 Ok(expr)
             }
             Err(left_err) => Err(left_err),
-        };
+        }
     }
 
     fn factor(&mut self) -> Result<Expr, ParseError> {
-        return match self.unary() {
+        match self.unary() {
             Ok(left) => {
                 let mut expr = left;
                 while self.match_token(&[TokenType::Slash, TokenType::Star]) {
-                    let operator = self.previous_token();
-                    match parse_binary_operator(operator) {
+                    let operator = self.previous_token().clone();
+                    match parse_binary_operator(&operator) {
                         Ok(binary_op) => {
                             match self.unary() {
                                 Ok(right) => {
-                                    expr = Expr::Binary(Box::new(expr), Box::new(right), binary_op);
+                                    expr = Expr::Binary(
+                                        Box::new(expr),
+                                        Box::new(right),
+                                        binary_op,
+                                        operator,
+                                    );
                                 }
                                 Err(right_err) => return Err(right_err),
                             };
@@ -410,7 +622,7 @@ impl Parser {
                 Ok(expr)
             }
             Err(left_err) => Err(left_err),
-        };
+        }
     }
 
     fn unary(&mut self) -> Result<Expr, ParseError> {
@@ -418,7 +630,7 @@ impl Parser {
             let operator_token = self.previous_token().clone();
             return match self.unary() {
                 Ok(expr) => match parse_unary_operator(&operator_token) {
-                    Ok(unary_op) => Ok(Expr::Unary(Box::new(expr), unary_op)),
+                    Ok(unary_op) => Ok(Expr::Unary(Box::new(expr), unary_op, operator_token)),
                     Err(err) => Err(err),
                 },
                 Err(err) => Err(err),
@@ -432,6 +644,9 @@ impl Parser {
         loop {
             if self.match_token(&[TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(&[TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, "property name after '.'")?;
+                expr = Expr::Get(Box::new(expr), name);
             } else {
                 break;
             }
@@ -449,49 +664,98 @@ impl Parser {
                 }
             }
         }
-        return self
-            .consume(TokenType::RightParen)
-            .and_then(|_| Ok(Expr::Call(Box::new(expr), arguments)));
+        self.consume(TokenType::RightParen, "')' after arguments")
+            .map(|paren| Expr::Call(Box::new(expr), arguments, paren))
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
         if self.match_token(&[TokenType::False]) {
-            return Ok(Expr::Literal(LiteralValue::Boolean(false)));
+            return Ok(Expr::Literal(
+                LiteralValue::Boolean(false),
+                self.previous_token().clone(),
+            ));
         }
         if self.match_token(&[TokenType::True]) {
-            return Ok(Expr::Literal(LiteralValue::Boolean(true)));
+            return Ok(Expr::Literal(
+                LiteralValue::Boolean(true),
+                self.previous_token().clone(),
+            ));
         }
         if self.match_token(&[TokenType::Nil]) {
-            return Ok(Expr::Literal(LiteralValue::Nil));
+            return Ok(Expr::Literal(
+                LiteralValue::Nil,
+                self.previous_token().clone(),
+            ));
+        }
+        if self.match_token(&[TokenType::Super]) {
+            let keyword = self.previous_token().clone();
+            self.consume(TokenType::Dot, "'.' after 'super'")?;
+            let method = self.consume(TokenType::Identifier, "superclass method name")?;
+            return Ok(Expr::Super(keyword, method, Cell::new(None)));
+        }
+        if self.match_token(&[TokenType::This]) {
+            return Ok(Expr::This(self.previous_token().clone(), Cell::new(None)));
         }
         if self.match_token(&[TokenType::Identifier]) {
-            return Ok(Expr::Variable(self.previous_token().clone()));
+            return Ok(Expr::Variable(
+                self.previous_token().clone(),
+                Cell::new(None),
+            ));
         }
 
-        if self.match_token(&[TokenType::Number]) {
-            let number = self.previous_token();
-            return match number.literal.as_ref().unwrap() {
-                Literal::String(string) => Ok(Expr::Literal(LiteralValue::String(string.clone()))),
-                Literal::Number(number) => Ok(Expr::Literal(LiteralValue::Number(*number))),
+        if self.match_token(&[TokenType::Number, TokenType::String]) {
+            let token = self.previous_token().clone();
+            let value = match token.literal.as_ref().unwrap() {
+                Literal::String(string) => LiteralValue::String(Rc::from(string.as_str())),
+                Literal::Number(number) => LiteralValue::Number(*number),
             };
+            return Ok(Expr::Literal(value, token));
+        }
+
+        if self.match_token(&[TokenType::Interpolation]) {
+            return self.interpolation();
         }
 
         if self.match_token(&[TokenType::LeftParen]) {
             let expr = self.expression();
 
-            if let Err(err) = self.consume(TokenType::RightParen) {
-                return Err(err);
-            }
+            self.consume(TokenType::RightParen, "')' after expression")?;
             return expr;
         }
 
         let last = self.peek().expect("No token found");
         Err(ParseError {
-            error_type: ErrorType::UnexpectedCharacter,
+            error_type: ErrorType::ExpectedExpression,
             token: last.clone(),
         })
     }
 
+    // the previous token is the string preceding the first interpolated expression
+    fn interpolation(&mut self) -> Result<Expr, ParseError> {
+        let mut parts: Vec<Expr> = vec![];
+        loop {
+            let token = self.previous_token();
+            if let Some(Literal::String(segment)) = &token.literal {
+                if !segment.is_empty() {
+                    let segment = LiteralValue::String(Rc::from(segment.as_str()));
+                    parts.push(Expr::Literal(segment, token.clone()));
+                }
+            }
+            if self.previous_token().token_type == TokenType::String {
+                return Ok(Expr::Interpolation(parts));
+            }
+
+            parts.push(self.expression()?);
+
+            if !self.match_token(&[TokenType::Interpolation, TokenType::String]) {
+                return Err(self.expected(
+                    vec![TokenType::Interpolation, TokenType::String],
+                    "'}' after interpolated expression",
+                ));
+            }
+        }
+    }
+
     fn match_token(&mut self, tokens: &[TokenType]) -> bool {
         for token in tokens {
             if self.check(token) {
@@ -502,35 +766,29 @@ impl Parser {
         false
     }
 
-    fn consume(&mut self, token_type: TokenType) -> Result<Token, ParseError> {
-        if let Some(next) = self.peek() {
-            let next_token = next.clone();
-            if next.token_type == token_type {
-                self.advance();
-                return Ok(next_token);
-            } else {
-                self.synchronize();
-                return Err(ParseError {
-                    error_type: ErrorType::UnexpectedCharacter,
-                    token: next_token,
-                });
-            }
+    // context describes what was expected, such as "')' after arguments"
+    fn consume(
+        &mut self,
+        token_type: TokenType,
+        context: &'static str,
+    ) -> Result<Token, ParseError> {
+        if self.check(&token_type) {
+            self.advance();
+            return Ok(self.previous_token().clone());
         }
+        Err(self.expected(vec![token_type], context))
+    }
 
-        return Err(ParseError {
-            error_type: ErrorType::UnexpectedCharacter,
-            token: Token {
-                token_type: TokenType::EOF,
-                lexeme: [].to_vec(),
-                line: 0,
-                literal: None,
-                column: 0,
-            },
-        });
+    fn expected(&self, expected: Vec<TokenType>, context: &'static str) -> ParseError {
+        let found = self.peek().or_else(|| self.tokens.last()).unwrap();
+        ParseError {
+            error_type: ErrorType::ExpectedToken { expected, context },
+            token: found.clone(),
+        }
     }
 
     fn advance(&mut self) {
-        self.current = self.current + 1;
+        self.current += 1;
     }
 
     fn check(&self, token_type: &TokenType) -> bool {
@@ -552,16 +810,18 @@ impl Parser {
         self.peek().map(|t| t.token_type) == Some(TokenType::EOF)
     }
 
+    // skips tokens up to the end of the current statement, stopping before the start of the next
+    // statement or block, or the end of the enclosing one
     fn synchronize(&mut self) {
-        self.advance();
-
         while !self.is_at_end() {
-            if self.previous_token().token_type == TokenType::Semicolon {
-                return;
-            }
-
             match self.peek().unwrap().token_type {
-                TokenType::Class
+                TokenType::Semicolon => {
+                    self.advance();
+                    return;
+                }
+                TokenType::LeftBrace
+                | TokenType::RightBrace
+                | TokenType::Class
                 | TokenType::Fun
                 | TokenType::Var
                 | TokenType::For
@@ -587,8 +847,6 @@ fn parse_binary_operator(token: &Token) -> Result<BinaryOperator, ParseError> {
         TokenType::GreaterEqual => Ok(BinaryOperator::GreaterEqual),
         TokenType::Less => Ok(BinaryOperator::Less),
         TokenType::LessEqual => Ok(BinaryOperator::LessEqual),
-        TokenType::And => Ok(BinaryOperator::And),
-        TokenType::Or => Ok(BinaryOperator::Or),
         _ => Err(ParseError {
             error_type: ErrorType::InvalidBinaryOperator,
             token: token.clone(),
@@ -610,34 +868,56 @@ fn parse_unary_operator(token: &Token) -> Result<UnaryOperator, ParseError> {
 #[allow(dead_code)]
 fn print_ast_expr(expr: &Expr) -> String {
     match expr {
-        Expr::Binary(left, right, op) => {
+        Expr::Binary(left, right, op, _) => {
             let l = print_ast_expr(left);
             let r = print_ast_expr(right);
             let oper = print_binary_op(op);
-            return format!("({} {} {})", oper, l, r);
+            format!("({} {} {})", oper, l, r)
         }
-        Expr::Unary(expr, op) => {
+        Expr::Logical(left, right, op) => {
+            let l = print_ast_expr(left);
+            let r = print_ast_expr(right);
+            let oper = match op {
+                LogicalOperator::And => "and",
+                LogicalOperator::Or => "or",
+            };
+            format!("({} {} {})", oper, l, r)
+        }
+        Expr::Unary(expr, op, _) => {
             let l = print_ast_expr(expr);
             let oper = print_unary_op(op);
-            return format!("{}{}", oper, l);
+            format!("{}{}", oper, l)
         }
-        Expr::Literal(lit) => match lit {
+        Expr::Literal(lit, _) => match lit {
             LiteralValue::Number(num) => num.to_string(),
-            LiteralValue::String(str) => str.to_string(),
+            LiteralValue::String(str) => format!("{:?}", str),
             LiteralValue::Boolean(bool) => bool.to_string(),
             LiteralValue::Nil => String::from("nil"),
         },
-        Expr::Variable(v) => {
-            return String::from_utf8(v.lexeme.clone()).unwrap();
+        Expr::Variable(v, _) => String::from_utf8(v.lexeme.clone()).unwrap(),
+        Expr::Assignment(name, value, _) => {
+            format!("{} = {}", name.symbol(), print_ast_expr(value))
         }
-        Expr::Assignment(name, value) => return format!("{} = {}", name, print_ast_expr(value)),
-        Expr::Call(expr, args) => {
+        Expr::Call(expr, args, _) => {
             let mut arg_str = String::new();
             for arg in args {
                 arg_str.push_str(&print_ast_expr(arg));
                 arg_str.push_str(", ");
             }
-            return format!("{}({})", print_ast_expr(expr), arg_str);
+            format!("{}({})", print_ast_expr(expr), arg_str)
+        }
+        Expr::Get(object, name) => format!("{}.{}", print_ast_expr(object), name.symbol()),
+        Expr::Set(object, name, value) => format!(
+            "{}.{} = {}",
+            print_ast_expr(object),
+            name.symbol(),
+            print_ast_expr(value)
+        ),
+        Expr::This(_, _) => String::from("this"),
+        Expr::Super(_, method, _) => format!("super.{}", method.symbol()),
+        Expr::Interpolation(parts) => {
+            let parts: Vec<String> = parts.iter().map(print_ast_expr).collect();
+            format!("(interpolate {})", parts.join(" "))
         }
     }
 }
@@ -646,13 +926,13 @@ fn print_ast_expr(expr: &Expr) -> String {
 fn print_ast(statement: &Statement) -> String {
     match statement {
         Statement::Expression(expr) => print_ast_expr(expr),
-        Statement::Print(expr) => format!("print {}", print_ast_expr(expr)),
+        Statement::Print(_, expr) => format!("print {}", print_ast_expr(expr)),
         Statement::Declaration(name, expr) => match expr {
             None => {
-                format!("var {};", lexeme_to_name(name))
+                format!("var {};", name.symbol())
             }
             Some(value) => {
-                format!("var {} = {}", lexeme_to_name(name), print_ast_expr(value))
+                format!("var {} = {}", name.symbol(), print_ast_expr(value))
             }
         },
         Statement::Block(statements) => print_block_ast(statements),
@@ -671,16 +951,20 @@ fn print_ast(statement: &Statement) -> String {
             }
             result
         }
-        Statement::Function {
-            name,
-            params,
-            block,
-        } => {
+        Statement::While { condition, body } => {
+            format!("while ({}) {}", print_ast_expr(condition), print_ast(body))
+        }
+        Statement::Function(declaration) => {
+            let FunctionDecl {
+                name,
+                params,
+                block,
+            } = declaration.as_ref();
             let mut result = String::from("fun ");
-            result.push_str(&lexeme_to_name(name));
-            result.push_str("(");
+            result.push_str(name.symbol().as_str());
+            result.push('(');
             for param in params {
-                result.push_str(&lexeme_to_name(param));
+                result.push_str(param.symbol().as_str());
                 result.push_str(", ");
             }
             result.push_str(") ");
@@ -688,6 +972,21 @@ fn print_ast(statement: &Statement) -> String {
             result
         }
         Statement::Return(_, _) => String::from("return"),
+        Statement::Class {
+            name,
+            superclass,
+            methods,
+        } => {
+            let mut result = String::from("class ");
+            result.push_str(name.symbol().as_str());
+            if let Some(superclass) = superclass {
+                result.push_str(" < ");
+                result.push_str(&print_ast_expr(superclass));
+            }
+            result.push(' ');
+            result.push_str(&print_block_ast(methods));
+            result
+        }
     }
 }
 
@@ -695,16 +994,16 @@ fn print_block_ast(statements: &Vec<Statement>) -> String {
     let mut result = String::from("{");
     for statement in statements {
         result.push_str(&print_ast(statement));
-        result.push_str(";");
+        result.push(';');
     }
-    result.push_str("}");
+    result.push('}');
     result
 }
 
 fn print_binary_op(op: &BinaryOperator) -> &str {
     match op {
-        BinaryOperator::Minus => "+",
-        BinaryOperator::Plus => "-",
+        BinaryOperator::Minus => "-",
+        BinaryOperator::Plus => "+",
         BinaryOperator::Slash => "/",
         BinaryOperator::Star => "*",
         BinaryOperator::BangEqual => "!=",
@@ -713,8 +1012,6 @@ fn print_binary_op(op: &BinaryOperator) -> &str {
         BinaryOperator::GreaterEqual => ">=",
         BinaryOperator::Less => "<",
         BinaryOperator::LessEqual => "<=",
-        BinaryOperator::And => "and",
-        BinaryOperator::Or => "or",
     }
 }
 
@@ -734,7 +1031,7 @@ mod tests {
     #[test]
     fn test_declaration() {
         let input = "var a = 3;";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
         let statement = statements.first().unwrap();
         assert_eq!(print_ast(statement), "var a = 3");
@@ -743,16 +1040,25 @@ mod tests {
     #[test]
     fn test_assignment() {
         let input = "a = 3;";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
         let statement = statements.first().unwrap();
         assert_eq!(print_ast(statement), "a = 3");
     }
 
+    #[test]
+    fn test_binary_operators() {
+        let input = "1 + 2 - 3;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "(- (+ 1 2) 3)");
+    }
+
     #[test]
     fn test_blocks() {
         let input = "{ var a = 3; print a; }";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
 
         let statements = parse(tokens).unwrap();
         let statement = statements.first().unwrap();
@@ -762,7 +1068,7 @@ mod tests {
     #[test]
     fn test_parser_with_declaration_statement() {
         let input = "var a = 3;";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
         assert_eq!(statements.len(), 1);
         let statement = &statements[0];
@@ -774,6 +1080,7 @@ mod tests {
                     literal: _,
                     line: _,
                     column: _,
+                    symbol: _,
                 },
                 expr,
             ) => {
@@ -781,7 +1088,7 @@ mod tests {
                 assert_eq!(lexeme, b"a");
 
                 match expr {
-                    Some(Expr::Literal(LiteralValue::Number(num))) => {
+                    Some(Expr::Literal(LiteralValue::Number(num), _)) => {
                         assert_eq!(num, &3.0);
                     }
                     _ => panic!("Expected literal expression"),
@@ -794,23 +1101,24 @@ mod tests {
     #[test]
     fn test_parser_with_fun_declaration_statement() {
         let input = "fun a() { print 3; }";
-        let tokens = scanner::scan(String::from(input));
+        let tokens = scanner::scan(String::from(input)).unwrap();
         let statements = parse(tokens).unwrap();
         assert_eq!(statements.len(), 1);
         let statement = &statements[0];
         match statement {
-            Statement::Function {
-                name,
-                params,
-                block,
-            } => {
+            Statement::Function(declaration) => {
+                let FunctionDecl {
+                    name,
+                    params,
+                    block,
+                } = declaration.as_ref();
                 assert_eq!(name.lexeme, b"a");
                 assert_eq!(params.len(), 0);
                 assert_eq!(block.len(), 1);
                 let print_statement = &block[0];
                 match print_statement {
-                    Statement::Print(expr) => match expr {
-                        Expr::Literal(LiteralValue::Number(num)) => {
+                    Statement::Print(_, expr) => match expr {
+                        Expr::Literal(LiteralValue::Number(num), _) => {
                             assert_eq!(num, &3.0);
                         }
                         _ => panic!("Expected literal expression"),
@@ -821,4 +1129,218 @@ mod tests {
             _ => panic!("Expected function declaration statement"),
         }
     }
+
+    #[test]
+    fn test_while() {
+        let input = "while (a < 3) { a = a + 1; }";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "while ((< a 3)) {a = (+ a 1);}");
+    }
+
+    #[test]
+    fn test_for_desugars_to_while() {
+        let input = "for (var i = 0; i < 3; i = i + 1) print i;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(
+            print_ast(statement),
+            "{var i = 0;while ((< i 3)) {print i;i = (+ i 1);};}"
+        );
+    }
+
+    #[test]
+    fn test_for_without_clauses() {
+        let input = "for (;;) print 1;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "while (true) print 1");
+    }
+
+    #[test]
+    fn test_class_declaration() {
+        let input = "class Point { init(x) { this.x = x; } getX() { return this.x; } }";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(
+            print_ast(statement),
+            "class Point {fun init(x, ) {this.x = x;};fun getX() {return;};}"
+        );
+    }
+
+    #[test]
+    fn test_property_access() {
+        let input = "a.b.c = d.e;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "a.b.c = d.e");
+    }
+
+    #[test]
+    fn test_subclass_declaration() {
+        let input = "class B < A { method() { return super.method(); } }";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(
+            print_ast(statement),
+            "class B < A {fun method() {return;};}"
+        );
+        match statement {
+            Statement::Class { methods, .. } => match &methods[0] {
+                Statement::Function(declaration) => match &declaration.block[0] {
+                    Statement::Return(_, Some(Expr::Call(callee, _, _))) => {
+                        assert_eq!(print_ast_expr(callee), "super.method");
+                    }
+                    _ => panic!("Expected return of a call"),
+                },
+                _ => panic!("Expected method"),
+            },
+            _ => panic!("Expected class declaration"),
+        }
+    }
+
+    #[test]
+    fn test_logical_precedence() {
+        let input = "a or b and c == d;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "(or a (and b (== c d)))");
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let input = "print \"hello\" + \"world\";";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "print (+ \"hello\" \"world\")");
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let input = "print \"total: ${a + b}, ${\"nested ${c}\"}!\";";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(
+            print_ast(statement),
+            "print (interpolate \"total: \" (+ a b) \", \" (interpolate \"nested \" c) \"!\")"
+        );
+    }
+
+    #[test]
+    fn test_unary() {
+        let input = "!-a == --1;";
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        let statement = statements.first().unwrap();
+        assert_eq!(print_ast(statement), "(== !-a --1)");
+    }
+
+    fn parse_errors(input: &str) -> Vec<ParseError> {
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        parse(tokens).unwrap_err()
+    }
+
+    #[test]
+    fn test_expected_token_errors() {
+        let errors = parse_errors("foo(1, 2;");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error_type,
+            ErrorType::ExpectedToken {
+                expected: vec![TokenType::RightParen],
+                context: "')' after arguments"
+            }
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "expected ')' after arguments, found ';'"
+        );
+
+        let errors = parse_errors("fun f(1) {}");
+        assert_eq!(errors[0].to_string(), "expected parameter name, found '1'");
+
+        let errors = parse_errors("var a = 1");
+        assert_eq!(
+            errors[0].to_string(),
+            "expected ';' after variable declaration, found end of input"
+        );
+    }
+
+    #[test]
+    fn test_expected_one_of_several_tokens() {
+        let errors = parse_errors("print \"${a b}\";");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].error_type,
+            ErrorType::ExpectedToken {
+                expected: vec![TokenType::Interpolation, TokenType::String],
+                context: "'}' after interpolated expression"
+            }
+        );
+        assert_eq!(errors[0].token.lexeme, b"b".to_vec());
+    }
+
+    #[test]
+    fn test_each_syntax_error_is_reported_once() {
+        let corpus: Vec<(&str, Vec<(usize, &str)>)> = vec![
+            (
+                include_str!("../samples/invalid-statements.cjj"),
+                vec![(2, "expected ';' after value, found 'print'")],
+            ),
+            (
+                "print a +;\nvar = 1;\nprint (1;\nprint 2;",
+                vec![
+                    (1, "expected expression, found ';'"),
+                    (2, "expected variable name, found '='"),
+                    (3, "expected ')' after expression, found ';'"),
+                ],
+            ),
+            (
+                "fun f(a, 1) { print a; }\nclass A < { }\nprint f(1);",
+                vec![
+                    (1, "expected parameter name, found '1'"),
+                    (2, "expected superclass name, found '{'"),
+                ],
+            ),
+            (
+                "{ print ; print 1; }\n}\nfun g() { var x = ; return x }",
+                vec![
+                    (1, "expected expression, found ';'"),
+                    (2, "expected expression, found '}'"),
+                    (3, "expected expression, found ';'"),
+                    (3, "expected ';' after return value, found '}'"),
+                ],
+            ),
+            (
+                "1 = 2; a + b = 3; print \"${a b}\"; print 1",
+                vec![
+                    (1, "invalid assignment target"),
+                    (1, "invalid assignment target"),
+                    (1, "expected '}' after interpolated expression, found 'b'"),
+                    (1, "expected ';' after value, found end of input"),
+                ],
+            ),
+        ];
+
+        for (source, expected) in corpus {
+            let errors: Vec<(usize, String)> = parse_errors(source)
+                .iter()
+                .map(|error| (error.token.line, error.to_string()))
+                .collect();
+            let expected: Vec<(usize, String)> = expected
+                .into_iter()
+                .map(|(line, message)| (line, String::from(message)))
+                .collect();
+            assert_eq!(errors, expected, "{}", source);
+        }
+    }
 }
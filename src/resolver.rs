@@ -0,0 +1,471 @@
+use crate::parser::{Expr, FunctionDecl, Local, Statement};
+use crate::scanner::Token;
+use crate::symbol::Symbol;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ResolveErrorType {
+    ReadLocalInOwnInitializer,
+    AlreadyDeclared,
+    ReturnOutsideFunction,
+    ReturnValueFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    InheritFromSelf,
+}
+
+impl Display for ResolveErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            ResolveErrorType::ReadLocalInOwnInitializer => {
+                "can't read local variable in its own initializer"
+            }
+            ResolveErrorType::AlreadyDeclared => "already a variable with this name in this scope",
+            ResolveErrorType::ReturnOutsideFunction => "can't return from top-level code",
+            ResolveErrorType::ReturnValueFromInitializer => {
+                "can't return a value from an initializer"
+            }
+            ResolveErrorType::ThisOutsideClass => "can't use 'this' outside of a class",
+            ResolveErrorType::SuperOutsideClass => "can't use 'super' outside of a class",
+            ResolveErrorType::SuperWithoutSuperclass => {
+                "can't use 'super' in a class with no superclass"
+            }
+            ResolveErrorType::InheritFromSelf => "a class can't inherit from itself",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResolveError {
+    pub error_type: ResolveErrorType,
+    pub token: Token,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+#[derive(Clone, Copy)]
+struct Binding {
+    // the order the name was declared in, which is where the interpreter stores its value
+    slot: usize,
+    // whether its initialiser has finished
+    defined: bool,
+}
+
+// Globals are not tracked. Scopes must line up with the environments the interpreter creates at
+// runtime, and declare names in the same order.
+struct Resolver {
+    scopes: Vec<HashMap<Symbol, Binding>>,
+    function_type: FunctionType,
+    class_type: ClassType,
+    errors: Vec<ResolveError>,
+}
+
+pub fn resolve(statements: &[Statement]) -> Result<(), Vec<ResolveError>> {
+    let mut resolver = Resolver::new();
+    resolver.resolve_statements(statements);
+
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver {
+            scopes: vec![],
+            function_type: FunctionType::None,
+            class_type: ClassType::None,
+            errors: vec![],
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(expr) | Statement::Print(_, expr) => {
+                self.resolve_expression(expr)
+            }
+            Statement::Declaration(name, initialiser) => {
+                self.declare(name);
+                if let Some(initialiser) = initialiser {
+                    self.resolve_expression(initialiser);
+                }
+                self.define(name);
+            }
+            Statement::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                self.resolve_statements(statements);
+                self.scopes.pop();
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+            Statement::Function(declaration) => {
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Statement::Return(keyword, value) => {
+                if self.function_type == FunctionType::None {
+                    self.error(ResolveErrorType::ReturnOutsideFunction, keyword);
+                }
+                if let Some(value) = value {
+                    if self.function_type == FunctionType::Initializer {
+                        self.error(ResolveErrorType::ReturnValueFromInitializer, keyword);
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Statement::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class_type = self.class_type;
+                self.class_type = ClassType::Class;
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable(superclass_name, _) = superclass {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(ResolveErrorType::InheritFromSelf, superclass_name);
+                        }
+                    }
+                    self.class_type = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+
+                    self.scopes.push(HashMap::new());
+                    self.define_symbol(Symbol::SUPER);
+                }
+
+                self.scopes.push(HashMap::new());
+                self.define_symbol(Symbol::THIS);
+                for method in methods {
+                    if let Statement::Function(declaration) = method {
+                        let function_type = if declaration.name.symbol() == Symbol::INIT {
+                            FunctionType::Initializer
+                        } else {
+                            FunctionType::Method
+                        };
+                        self.resolve_function(declaration, function_type);
+                    }
+                }
+                self.scopes.pop();
+
+                if superclass.is_some() {
+                    self.scopes.pop();
+                }
+                self.class_type = enclosing_class_type;
+            }
+        }
+    }
+
+    // parameters and the body share one scope, as they do in the interpreter's call environment
+    fn resolve_function(&mut self, declaration: &FunctionDecl, function_type: FunctionType) {
+        let FunctionDecl { params, block, .. } = declaration;
+        let enclosing_function_type = self.function_type;
+        self.function_type = function_type;
+
+        self.scopes.push(HashMap::new());
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(block);
+        self.scopes.pop();
+
+        self.function_type = enclosing_function_type;
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Binary(left, right, _, _) | Expr::Logical(left, right, _) => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Unary(expr, _, _) => self.resolve_expression(expr),
+            Expr::Literal(..) => {}
+            Expr::Variable(name, depth) => {
+                let in_own_initialiser = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.symbol()))
+                    .is_some_and(|binding| !binding.defined);
+                if in_own_initialiser {
+                    self.error(ResolveErrorType::ReadLocalInOwnInitializer, name);
+                }
+                depth.set(self.resolve_local(name.symbol()));
+            }
+            Expr::Assignment(name, value, depth) => {
+                self.resolve_expression(value);
+                depth.set(self.resolve_local(name.symbol()));
+            }
+            Expr::Call(callee, args, _) => {
+                self.resolve_expression(callee);
+                for arg in args {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expr::Get(object, _) => self.resolve_expression(object),
+            Expr::Set(object, _, value) => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expr::This(keyword, depth) => {
+                if self.class_type == ClassType::None {
+                    self.error(ResolveErrorType::ThisOutsideClass, keyword);
+                }
+                depth.set(self.resolve_local(Symbol::THIS));
+            }
+            Expr::Super(keyword, _, depth) => match self.class_type {
+                ClassType::None => self.error(ResolveErrorType::SuperOutsideClass, keyword),
+                ClassType::Class => self.error(ResolveErrorType::SuperWithoutSuperclass, keyword),
+                ClassType::Subclass => depth.set(self.resolve_local(Symbol::SUPER)),
+            },
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    self.resolve_expression(part);
+                }
+            }
+        }
+    }
+
+    fn resolve_local(&self, name: Symbol) -> Option<Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                scope.get(&name).map(|binding| Local {
+                    depth,
+                    slot: binding.slot,
+                })
+            })
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let already_declared = match self.scopes.last_mut() {
+            None => return,
+            Some(scope) => {
+                let binding = Binding {
+                    slot: scope.len(),
+                    defined: false,
+                };
+                scope.insert(name.symbol(), binding).is_some()
+            }
+        };
+        if already_declared {
+            self.error(ResolveErrorType::AlreadyDeclared, name);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_symbol(name.symbol());
+    }
+
+    fn define_symbol(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let slot = scope.len();
+            scope
+                .entry(name)
+                .or_insert(Binding {
+                    slot,
+                    defined: false,
+                })
+                .defined = true;
+        }
+    }
+
+    fn error(&mut self, error_type: ResolveErrorType, token: &Token) {
+        self.errors.push(ResolveError {
+            error_type,
+            token: token.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::scanner;
+
+    fn resolve_source(input: &str) -> Result<Vec<Statement>, Vec<ResolveError>> {
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).map(|_| statements)
+    }
+
+    fn error_types(input: &str) -> Vec<ResolveErrorType> {
+        resolve_source(input)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.error_type)
+            .collect()
+    }
+
+    #[test]
+    fn test_globals_are_unresolved() {
+        let statements = resolve_source("var a = 1; a = a + 1;").unwrap();
+        match &statements[1] {
+            Statement::Expression(Expr::Assignment(_, value, local)) => {
+                assert_eq!(local.get(), None);
+                match value.as_ref() {
+                    Expr::Binary(left, _, _, _) => {
+                        assert!(
+                            matches!(left.as_ref(), Expr::Variable(_, local) if local.get().is_none())
+                        )
+                    }
+                    _ => panic!("Expected binary expression"),
+                }
+            }
+            _ => panic!("Expected assignment"),
+        }
+    }
+
+    #[test]
+    fn test_local_depths_and_slots() {
+        let statements = resolve_source(
+            "
+            fun outer(z, a) {
+                {
+                    var y;
+                    var b = a;
+                    fun inner() {
+                        return b;
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let block = match &statements[0] {
+            Statement::Function(declaration) => match &declaration.block[0] {
+                Statement::Block(statements) => statements,
+                _ => panic!("Expected block"),
+            },
+            _ => panic!("Expected function"),
+        };
+        match &block[1] {
+            Statement::Declaration(_, Some(Expr::Variable(_, local))) => {
+                assert_eq!(local.get(), Some(Local { depth: 1, slot: 1 }))
+            }
+            _ => panic!("Expected declaration"),
+        }
+        match &block[2] {
+            Statement::Function(declaration) => match &declaration.block[0] {
+                Statement::Return(_, Some(Expr::Variable(_, local))) => {
+                    assert_eq!(local.get(), Some(Local { depth: 1, slot: 1 }))
+                }
+                _ => panic!("Expected return"),
+            },
+            _ => panic!("Expected function"),
+        }
+    }
+
+    #[test]
+    fn test_resolve_shared_function_body() {
+        let tokens = scanner::scan(String::from("fun f(a) { return a; }")).unwrap();
+        let statements = parse(tokens).unwrap();
+        // the clone shares the function body, so resolving must not need sole ownership of it
+        let copy = statements.clone();
+        resolve(&statements).unwrap();
+        match &copy[0] {
+            Statement::Function(declaration) => match &declaration.block[0] {
+                Statement::Return(_, Some(Expr::Variable(_, local))) => {
+                    assert_eq!(local.get(), Some(Local { depth: 0, slot: 0 }))
+                }
+                _ => panic!("Expected return"),
+            },
+            _ => panic!("Expected function"),
+        }
+    }
+
+    #[test]
+    fn test_read_local_in_own_initializer() {
+        assert_eq!(
+            error_types("var a = 1; { var a = a + 2; }"),
+            vec![ResolveErrorType::ReadLocalInOwnInitializer]
+        );
+        assert!(resolve_source("var a = 1; var a = a + 2;").is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_declaration() {
+        assert_eq!(
+            error_types("fun f(a) { var a = 1; } { var b; var b; }"),
+            vec![
+                ResolveErrorType::AlreadyDeclared,
+                ResolveErrorType::AlreadyDeclared
+            ]
+        );
+        assert!(resolve_source("var a; var a; { var a; { var a; } }").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_return() {
+        assert_eq!(
+            error_types("return 1;"),
+            vec![ResolveErrorType::ReturnOutsideFunction]
+        );
+        assert_eq!(
+            error_types("class A { init() { return 1; } }"),
+            vec![ResolveErrorType::ReturnValueFromInitializer]
+        );
+        assert!(resolve_source("class A { init() { return; } }").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_this_and_super() {
+        assert_eq!(
+            error_types("print this; fun f() { return super.f; }"),
+            vec![
+                ResolveErrorType::ThisOutsideClass,
+                ResolveErrorType::SuperOutsideClass
+            ]
+        );
+        assert_eq!(
+            error_types("class A { f() { return super.f(); } }"),
+            vec![ResolveErrorType::SuperWithoutSuperclass]
+        );
+        assert_eq!(
+            error_types("class A < A {}"),
+            vec![ResolveErrorType::InheritFromSelf]
+        );
+    }
+}
@@ -1,14 +1,25 @@
+use crate::symbol::Symbol;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use unicode_ident::{is_xid_continue, is_xid_start};
 
 pub struct Scanner {
     current: usize,
     start: usize,
     tokens: Vec<Token>,
-    source: Vec<u8>,
-    line: u16,
+    errors: Vec<ScanError>,
+    interpolations: Vec<Interpolation>,
+    source: Vec<char>,
+    // lines and columns count from 1, with columns being characters from line_start
+    line: usize,
+    line_start: usize,
+    // where the token being scanned starts
+    token_line: usize,
+    token_column: usize,
     keywords: HashMap<String, TokenType>,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum TokenType {
     // Single-character tokens.
@@ -37,6 +48,9 @@ pub enum TokenType {
     // Literals.
     Identifier,
     String,
+    // The part of a string literal preceding a `${`, followed by the tokens of the
+    // interpolated expression and then the rest of the string.
+    Interpolation,
     Number,
 
     // Keywords.
@@ -60,6 +74,25 @@ pub enum TokenType {
     EOF,
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum StringKind {
+    // "...", with escape sequences
+    Escaped,
+    // """...""", with escape sequences and unescaped quotes
+    MultiLine,
+    // r"...", taken exactly as written
+    Raw,
+}
+
+// A string literal that is part way through an interpolated `${...}` expression
+struct Interpolation {
+    kind: StringKind,
+    // braces opened inside the expression that have not been closed yet
+    open_braces: usize,
+    line: usize,
+    column: usize,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     String(String),
@@ -70,16 +103,63 @@ pub enum Literal {
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Vec<u8>,
-    pub line: u16,
+    pub line: usize,
     pub literal: Option<Literal>,
     pub column: usize,
+    // identifiers are interned as they are scanned
+    pub symbol: Option<Symbol>,
+}
+
+impl Token {
+    // the parser only ever uses identifiers as names
+    pub fn symbol(&self) -> Symbol {
+        self.symbol.expect("only identifiers have a symbol")
+    }
+
+    // characters the token covers in the source, with the end of input counting as one
+    pub fn length(&self) -> usize {
+        match self.token_type {
+            TokenType::EOF => 1,
+            _ => String::from_utf8_lossy(&self.lexeme).chars().count(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ScanErrorType {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    InvalidEscapeSequence,
+    InvalidNumber,
+}
+
+impl Display for ScanErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanErrorType::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            ScanErrorType::UnterminatedString => write!(f, "unterminated string"),
+            ScanErrorType::InvalidEscapeSequence => write!(f, "invalid escape sequence"),
+            ScanErrorType::InvalidNumber => write!(f, "invalid number"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ScanError {
+    pub error_type: ScanErrorType,
+    pub line: usize,
+    pub column: usize,
 }
 
-pub fn scan(input: String) -> Vec<Token> {
+pub fn scan(input: String) -> Result<Vec<Token>, Vec<ScanError>> {
     let mut scanner = Scanner::new(input);
     scanner.scan();
 
-    scanner.tokens
+    if scanner.errors.is_empty() {
+        Ok(scanner.tokens)
+    } else {
+        Err(scanner.errors)
+    }
 }
 
 impl Scanner {
@@ -106,31 +186,66 @@ impl Scanner {
         .map(|(k, v)| (String::from(k), v))
         .collect();
 
-        return Scanner {
+        Scanner {
             current: 0,
             start: 0,
             tokens: Vec::new(),
-            line: 0,
-            source: source.into_bytes(),
+            errors: Vec::new(),
+            interpolations: Vec::new(),
+            line: 1,
+            line_start: 0,
+            token_line: 1,
+            token_column: 1,
+            source: source.chars().collect(),
             keywords,
-        };
+        }
     }
 
     pub fn scan(&mut self) {
         while self.current < self.source.len() {
             self.scan_next();
         }
+        // everything still open is inside the outermost literal, which is reported once
+        if let Some(outermost) = self.interpolations.first() {
+            self.errors.push(ScanError {
+                error_type: ScanErrorType::UnterminatedString,
+                line: outermost.line,
+                column: outermost.column,
+            });
+            self.interpolations.clear();
+        }
+        self.start_token();
         self.add_token(TokenType::EOF);
     }
 
     fn scan_next(&mut self) {
-        let c = self.source[self.current] as char;
-        self.start = self.current;
+        let c = self.source[self.current];
+        self.start_token();
         self.advance();
         match c {
             ';' => self.add_token(TokenType::Semicolon),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.open_braces += 1;
+                }
+                self.add_token(TokenType::LeftBrace);
+            }
+            '}' => match self.interpolations.pop() {
+                Some(interpolation) if interpolation.open_braces == 0 => {
+                    // the end of an interpolated expression, so carry on with the string
+                    self.continue_string_literal(
+                        interpolation.kind,
+                        interpolation.line,
+                        interpolation.column,
+                    );
+                }
+                Some(mut interpolation) => {
+                    interpolation.open_braces -= 1;
+                    self.interpolations.push(interpolation);
+                    self.add_token(TokenType::RightBrace);
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
             ',' => self.add_token(TokenType::Comma),
@@ -142,31 +257,59 @@ impl Scanner {
             '!' => self.add_double_token('=', TokenType::BangEqual, TokenType::Bang),
             '>' => self.add_double_token('=', TokenType::GreaterEqual, TokenType::Greater),
             '<' => self.add_double_token('=', TokenType::LessEqual, TokenType::Less),
-            '"' => self.add_string_literal(),
+            '"' => {
+                if self.peek() == '"' && self.peek_next() == '"' {
+                    self.advance();
+                    self.advance();
+                    self.add_string_literal(StringKind::MultiLine);
+                } else {
+                    self.add_string_literal(StringKind::Escaped);
+                }
+            }
+            'r' if self.peek() == '"' => {
+                self.advance();
+                self.add_string_literal(StringKind::Raw);
+            }
             '/' => {
                 let n = self.peek();
                 if n != '/' {
                     self.add_token(TokenType::Slash)
                 } else {
-                    while self.peek() != '\n' {
+                    while self.peek() != '\n' && self.current < self.source.len() {
                         self.advance();
                     }
                 }
             }
-            '\n' => self.line = self.line + 1,
+            '\n' => self.new_line(),
             ' ' | '\t' | '\r' => {}
             _ => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.add_number_literal();
-                } else if c.is_alphanumeric() {
+                } else if is_identifier_start(c) {
                     self.add_identifier();
                 } else {
-                    println!("Unrecognised character {}", c);
+                    self.error(ScanErrorType::UnexpectedCharacter(c));
                 }
             }
         }
     }
 
+    fn start_token(&mut self) {
+        self.start = self.current;
+        self.token_line = self.line;
+        self.token_column = self.column(self.start);
+    }
+
+    // expects the scanner to have just moved past a newline
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn column(&self, offset: usize) -> usize {
+        offset - self.line_start + 1
+    }
+
     fn add_double_token(&mut self, next_char: char, lhs: TokenType, rhs: TokenType) {
         if self.peek() == next_char {
             self.advance();
@@ -178,73 +321,177 @@ impl Scanner {
 
     fn peek(&self) -> char {
         if self.current < self.source.len() {
-            self.source[self.current] as char
+            self.source[self.current]
         } else {
             '\0'
         }
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 < self.source.len() {
-            self.source[self.current + 1] as char
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, offset: usize) -> char {
+        if self.current + offset < self.source.len() {
+            self.source[self.current + offset]
         } else {
             '\0'
         }
     }
 
     fn advance(&mut self) {
-        self.current = self.current + 1;
+        self.current += 1;
     }
 
     fn add_token(&mut self, token: TokenType) {
         self.add_token_with_literal(token, None);
     }
 
-    fn add_string_literal(&mut self) {
-        while self.peek() != '"' {
-            if self.peek() == '\n' {
-                self.line = self.line + 1;
+    fn add_string_literal(&mut self, kind: StringKind) {
+        self.continue_string_literal(kind, self.token_line, self.token_column);
+    }
+
+    // scans up to the end of the string or the start of the next interpolated expression, with
+    // the line and column being the start of the whole literal for error reporting
+    fn continue_string_literal(
+        &mut self,
+        kind: StringKind,
+        start_line: usize,
+        start_column: usize,
+    ) {
+        let mut value = String::new();
+
+        loop {
+            if self.current >= self.source.len() {
+                // a literal inside an interpolation is left to the enclosing literal to report
+                if self.interpolations.is_empty() {
+                    self.errors.push(ScanError {
+                        error_type: ScanErrorType::UnterminatedString,
+                        line: start_line,
+                        column: start_column,
+                    });
+                }
+                return;
+            }
+
+            let c = self.peek();
+            if c == '$' && self.peek_next() == '{' && kind != StringKind::Raw {
+                self.current += 2;
+                let string = Literal::String(value);
+                self.add_token_with_literal(TokenType::Interpolation, Some(string));
+                self.interpolations.push(Interpolation {
+                    kind,
+                    open_braces: 0,
+                    line: start_line,
+                    column: start_column,
+                });
+                return;
+            }
+            if c == '"' {
+                match kind {
+                    StringKind::MultiLine => {
+                        if self.peek_next() == '"' && self.peek_at(2) == '"' {
+                            self.current += 3;
+                            break;
+                        }
+                    }
+                    StringKind::Escaped | StringKind::Raw => {
+                        self.advance();
+                        break;
+                    }
+                }
+            }
+
+            if c == '\\' && kind != StringKind::Raw {
+                self.add_escape_sequence(&mut value);
+                continue;
             }
+
+            value.push(c);
             self.advance();
+            if c == '\n' {
+                self.new_line();
+            }
         }
-        // advance after final "
-        self.advance();
 
-        let string = Literal::String(
-            String::from_utf8(self.source[self.start + 1..self.current - 1].to_vec()).unwrap(),
-        );
+        let string = Literal::String(value);
         self.add_token_with_literal(TokenType::String, Some(string));
     }
 
+    // expects the scanner to be on the backslash, pushing the escaped character onto value
+    fn add_escape_sequence(&mut self, value: &mut String) {
+        let escape_start = self.current;
+        self.advance();
+
+        let escaped = match self.peek() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            '$' => Some('$'),
+            'u' if self.peek_next() == '{' => {
+                self.current += 2;
+                let digits_start = self.current;
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let digits: String = self.source[digits_start..self.current].iter().collect();
+                if self.peek() == '}' {
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        match escaped {
+            Some(c) => {
+                value.push(c);
+                self.advance();
+            }
+            None => self.errors.push(ScanError {
+                error_type: ScanErrorType::InvalidEscapeSequence,
+                line: self.line,
+                column: self.column(escape_start),
+            }),
+        }
+    }
+
     fn add_number_literal(&mut self) {
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance(); // consume .
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
 
-        let num: f64 = self.get_current_string().parse().unwrap();
-        let num_literal = Literal::Number(num);
-        self.add_token_with_literal(TokenType::Number, Some(num_literal));
+        match self.get_current_string().parse() {
+            Ok(num) => self.add_token_with_literal(TokenType::Number, Some(Literal::Number(num))),
+            Err(_) => self.error(ScanErrorType::InvalidNumber),
+        }
     }
 
     fn get_current_string(&mut self) -> String {
-        String::from_utf8(self.source[self.start..self.current].to_vec()).unwrap()
+        self.source[self.start..self.current].iter().collect()
     }
 
     fn add_identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while is_identifier_continue(self.peek()) {
             self.advance();
         }
 
         let str = self.get_current_string();
-        let token = self.keywords.get(str.as_str()).clone();
+        let token = self.keywords.get(str.as_str());
         if let Some(t) = token {
             self.add_token(*t);
         } else {
@@ -252,18 +499,41 @@ impl Scanner {
         }
     }
 
+    // reports an error at the start of the current token
+    fn error(&mut self, error_type: ScanErrorType) {
+        self.errors.push(ScanError {
+            error_type,
+            line: self.token_line,
+            column: self.token_column,
+        });
+    }
+
     fn add_token_with_literal(&mut self, token: TokenType, literal: Option<Literal>) {
-        let lexeme = self.source[self.start..self.current].to_vec();
+        let lexeme = self.get_current_string();
+        let symbol = match token {
+            TokenType::Identifier => Some(Symbol::intern(&lexeme)),
+            _ => None,
+        };
         self.tokens.push(Token {
-            lexeme,
+            lexeme: lexeme.into_bytes(),
             literal,
             token_type: token,
-            line: self.line,
-            column: self.start,
+            line: self.token_line,
+            column: self.token_column,
+            symbol,
         });
     }
 }
 
+// Unicode's XID_Start and XID_Continue, with underscores allowed anywhere
+fn is_identifier_start(c: char) -> bool {
+    is_xid_start(c) || c == '_'
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    is_xid_continue(c)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,7 +550,8 @@ mod tests {
             Vec::from([
                 TokenType::Semicolon,
                 TokenType::LeftBrace,
-                TokenType::RightBrace
+                TokenType::RightBrace,
+                TokenType::EOF
             ])
         );
     }
@@ -292,7 +563,7 @@ mod tests {
         scanner.scan();
 
         assert_eq!(
-            scanner.tokens.get(0).unwrap().token_type,
+            scanner.tokens.first().unwrap().token_type,
             TokenType::EqualEqual
         );
 
@@ -300,7 +571,7 @@ mod tests {
         let mut scanner = Scanner::new(input);
         scanner.scan();
 
-        assert_eq!(scanner.tokens.get(0).unwrap().token_type, TokenType::Equal);
+        assert_eq!(scanner.tokens.first().unwrap().token_type, TokenType::Equal);
     }
 
     #[test]
@@ -309,39 +580,34 @@ mod tests {
         let mut scanner = Scanner::new(input);
         scanner.scan();
 
-        assert_eq!(scanner.tokens.len(), 0);
+        assert_eq!(scanner.tokens.len(), 1);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::EOF);
 
         let input = String::from("/");
         let mut scanner = Scanner::new(input);
         scanner.scan();
 
-        assert_eq!(scanner.tokens.get(0).unwrap().token_type, TokenType::Slash);
-    }
-
-    // // Having trouble with this test
-    // #[test]
-    // fn string_literal_tokens() {
-    //     let input = String::from("\"hello\"");
-    //     let mut scanner = Scanner::new(input);
-    //     scanner.scan();
-    //
-    //     let token = scanner.tokens.get(0).unwrap();
-    //     assert_eq!(token.token_type, TokenType::String);
-    //     let expected_str = String::from("hello");
-    //     assert!(matches!(
-    //         token.literal.as_ref().unwrap(),
-    //         Literal::String(expected_str)
-    //     ));
-    // }
-
-    // Having trouble with this test
+        assert_eq!(scanner.tokens.first().unwrap().token_type, TokenType::Slash);
+    }
+
+    #[test]
+    fn string_literal_tokens() {
+        let input = String::from("\"hello\"");
+        let mut scanner = Scanner::new(input);
+        scanner.scan();
+
+        let token = scanner.tokens.first().unwrap();
+        assert_eq!(token.token_type, TokenType::String);
+        assert_eq!(token.literal, Some(Literal::String(String::from("hello"))));
+    }
+
     #[test]
     fn number_literal_tokens() {
         let input = String::from("10.1234");
         let mut scanner = Scanner::new(input);
         scanner.scan();
 
-        let token = scanner.tokens.get(0).unwrap();
+        let token = scanner.tokens.first().unwrap();
         assert_eq!(token.token_type, TokenType::Number);
     }
 
@@ -351,7 +617,7 @@ mod tests {
         let mut scanner = Scanner::new(input);
         scanner.scan();
 
-        let token = scanner.tokens.get(0).unwrap();
+        let token = scanner.tokens.first().unwrap();
         assert_eq!(token.token_type, TokenType::While);
     }
 
@@ -366,6 +632,260 @@ mod tests {
         let mut scanner = Scanner::new(input);
         scanner.scan();
 
-        assert_eq!(scanner.tokens.len(), 16);
+        assert_eq!(scanner.tokens.len(), 17);
+    }
+
+    #[test]
+    fn string_escape_sequences() {
+        let input = String::from(r#""a\tb\n\"c\" \\ \u{e9}\u{1F600}""#);
+        let tokens = scan(input).unwrap();
+
+        assert_eq!(
+            tokens[0].literal,
+            Some(Literal::String(String::from(
+                "a\tb\n\"c\" \\ \u{e9}\u{1F600}"
+            )))
+        );
+    }
+
+    #[test]
+    fn invalid_escape_sequences() {
+        let input = String::from(r#""\q" "\u{110000}" "\u{41""#);
+        let errors = scan(input).unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors
+            .iter()
+            .all(|e| e.error_type == ScanErrorType::InvalidEscapeSequence));
+        assert_eq!(errors[0].line, 1);
+        assert_eq!(errors[0].column, 2);
+    }
+
+    #[test]
+    fn multi_line_string_literal() {
+        let input = String::from("\"\"\"first \"line\"\nsecond\\tline\"\"\" 1");
+        let tokens = scan(input).unwrap();
+
+        assert_eq!(
+            tokens[0].literal,
+            Some(Literal::String(String::from(
+                "first \"line\"\nsecond\tline"
+            )))
+        );
+        assert_eq!(tokens[0].line, 1);
+        assert_eq!(tokens[1].token_type, TokenType::Number);
+        assert_eq!((tokens[1].line, tokens[1].column), (2, 17));
+    }
+
+    #[test]
+    fn raw_string_literal() {
+        let input = String::from(r#"r"C:\temp\new" r"#);
+        let mut scanner = Scanner::new(input);
+        scanner.scan();
+
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Literal::String(String::from(r"C:\temp\new")))
+        );
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn unterminated_string_literal() {
+        let input = String::from("var a = 1;\nvar b = \"never\nclosed;");
+        let errors = scan(input).unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![ScanError {
+                error_type: ScanErrorType::UnterminatedString,
+                line: 2,
+                column: 9,
+            }]
+        );
+    }
+
+    #[test]
+    fn interpolated_string_tokens() {
+        let input = String::from("\"total: ${a + b} of ${ {c} }\"");
+        let tokens = scan(input).unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            token_types,
+            vec![
+                TokenType::Interpolation,
+                TokenType::Identifier,
+                TokenType::Plus,
+                TokenType::Identifier,
+                TokenType::Interpolation,
+                TokenType::LeftBrace,
+                TokenType::Identifier,
+                TokenType::RightBrace,
+                TokenType::String,
+                TokenType::EOF,
+            ]
+        );
+        assert_eq!(
+            tokens[0].literal,
+            Some(Literal::String(String::from("total: ")))
+        );
+        assert_eq!(
+            tokens[4].literal,
+            Some(Literal::String(String::from(" of ")))
+        );
+        assert_eq!(tokens[8].literal, Some(Literal::String(String::new())));
+    }
+
+    #[test]
+    fn escaped_and_raw_interpolation() {
+        let input = String::from(r#""\${a}" r"${a}""#);
+        let tokens = scan(input).unwrap();
+
+        assert_eq!(
+            tokens[0].literal,
+            Some(Literal::String(String::from("${a}")))
+        );
+        assert_eq!(
+            tokens[1].literal,
+            Some(Literal::String(String::from("${a}")))
+        );
+    }
+
+    #[test]
+    fn unterminated_interpolation() {
+        let input = String::from("\"value: ${a");
+        let errors = scan(input).unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![ScanError {
+                error_type: ScanErrorType::UnterminatedString,
+                line: 1,
+                column: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn unterminated_literal_inside_interpolation() {
+        let outermost = vec![ScanError {
+            error_type: ScanErrorType::UnterminatedString,
+            line: 1,
+            column: 9,
+        }];
+
+        assert_eq!(scan(String::from("print 1 \"${\"")).unwrap_err(), outermost);
+        assert_eq!(
+            scan(String::from("print 1 \"${ \"${a} b")).unwrap_err(),
+            outermost
+        );
+    }
+
+    #[test]
+    fn trailing_comment_without_newline() {
+        let tokens = scan(String::from("print 1; // done")).unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[3].token_type, TokenType::EOF);
+    }
+
+    #[test]
+    fn lines_past_u16_max() {
+        let mut input = "\n".repeat(70000);
+        input.push_str("print 1;");
+        let tokens = scan(input).unwrap();
+
+        assert_eq!(tokens[0].line, 70001);
+    }
+
+    #[test]
+    fn unexpected_characters_are_all_reported() {
+        let input = String::from("var a = 1 # 2;\nprint a @ €;");
+        let errors = scan(input).unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                ScanError {
+                    error_type: ScanErrorType::UnexpectedCharacter('#'),
+                    line: 1,
+                    column: 11,
+                },
+                ScanError {
+                    error_type: ScanErrorType::UnexpectedCharacter('@'),
+                    line: 2,
+                    column: 9,
+                },
+                ScanError {
+                    error_type: ScanErrorType::UnexpectedCharacter('€'),
+                    line: 2,
+                    column: 11,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_input_never_panics() {
+        let input = "var é = \"a\\u{41}${b + \"c\"}\"; r\"x\" \"\"\"y\"\"\" 1.5 // end";
+        for end in (0..=input.len()).filter(|end| input.is_char_boundary(*end)) {
+            let _ = scan(String::from(&input[..end]));
+        }
+
+        assert_eq!(
+            scan(String::from("\"abc\\")).unwrap_err()[0].error_type,
+            ScanErrorType::InvalidEscapeSequence
+        );
+        assert_eq!(
+            scan(String::from("\"")).unwrap_err()[0].error_type,
+            ScanErrorType::UnterminatedString
+        );
+    }
+
+    #[test]
+    fn unicode_identifiers_and_strings() {
+        let input = String::from("var café_au_lait = \"naïve ☕\"; // ünïcödé\n_π2 = 1;");
+        let tokens = scan(input).unwrap();
+
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].lexeme, "café_au_lait".as_bytes());
+        assert_eq!(tokens[1].symbol(), Symbol::intern("café_au_lait"));
+        assert_eq!(tokens[3].symbol, None);
+        assert_eq!(
+            tokens[3].literal,
+            Some(Literal::String(String::from("naïve ☕")))
+        );
+        assert_eq!(tokens[5].lexeme, "_π2".as_bytes());
+        assert_eq!((tokens[5].line, tokens[5].column), (2, 1));
+    }
+
+    #[test]
+    fn identifiers_follow_xid_continue() {
+        // combining marks continue an identifier, but other numbers like ² and ½ don't
+        let tokens = scan(String::from("e\u{301}x")).unwrap();
+        assert_eq!(tokens[0].lexeme, "e\u{301}x".as_bytes());
+
+        let errors = scan(String::from("x² ½")).unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| &error.error_type)
+                .collect::<Vec<_>>(),
+            vec![
+                &ScanErrorType::UnexpectedCharacter('²'),
+                &ScanErrorType::UnexpectedCharacter('½')
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let input = String::from("\"日本語\" + ñ;");
+        let tokens = scan(input).unwrap();
+
+        assert_eq!(tokens[1].column, 7);
+        assert_eq!(tokens[2].column, 9);
+        assert_eq!(tokens[3].column, 10);
     }
 }
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+// An interned name, so names are compared and hashed as a single number. There is one interner
+// for the whole process, so a symbol means the same name on every thread. Every name interned
+// lives for the rest of the program, which is fine for identifiers in source code.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Symbol(u32);
+
+// names the interpreter looks up itself, interned ahead of everything else
+const PREDEFINED: [&str; 3] = ["init", "this", "super"];
+
+impl Symbol {
+    pub const INIT: Symbol = Symbol(0);
+    pub const THIS: Symbol = Symbol(1);
+    pub const SUPER: Symbol = Symbol(2);
+
+    pub fn intern(name: &str) -> Symbol {
+        interner().intern(name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().names[self.0 as usize]
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+impl Interner {
+    fn new() -> Self {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            names: vec![],
+        };
+        for name in PREDEFINED.iter() {
+            interner.intern(name);
+        }
+        interner
+    }
+
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let name: &'static str = Box::leak(Box::from(name));
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+}
+
+static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+fn interner() -> MutexGuard<'static, Interner> {
+    INTERNER
+        .get_or_init(|| Mutex::new(Interner::new()))
+        .lock()
+        // interning never leaves the tables half updated, so a panic elsewhere can't corrupt them
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning_the_same_name_gives_the_same_symbol() {
+        let a = Symbol::intern("count");
+        let b = Symbol::intern(&String::from("count"));
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("counter"));
+        assert_eq!(a.as_str(), "count");
+        assert_eq!(Symbol::intern("café").to_string(), "café");
+    }
+
+    #[test]
+    fn test_symbols_are_shared_between_threads() {
+        let symbol = std::thread::spawn(|| Symbol::intern("from another thread"))
+            .join()
+            .unwrap();
+        assert_eq!(symbol.as_str(), "from another thread");
+        assert_eq!(Symbol::intern("from another thread"), symbol);
+    }
+
+    #[test]
+    fn test_predefined_symbols() {
+        assert_eq!(Symbol::intern("init"), Symbol::INIT);
+        assert_eq!(Symbol::intern("this"), Symbol::THIS);
+        assert_eq!(Symbol::intern("super"), Symbol::SUPER);
+    }
+}
@@ -0,0 +1,1067 @@
+use crate::chunk::{Chunk, Constant, FunctionProto, OpCode};
+use crate::gc::Heap;
+use crate::interpreter::{check_arity, CallSite, RuntimeError, RuntimeErrorType};
+use crate::parser::UnaryOperator;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+
+const FRAMES_MAX: usize = 1024;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub callable: fn(args: &[Value]) -> Result<Value, RuntimeErrorType>,
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "function {}()", self.name)
+    }
+}
+
+pub struct Closure {
+    pub function: Rc<FunctionProto>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+// An upvalue points at a stack slot while the captured local is in scope, and holds the value
+// itself once the local has gone out of scope. A closure that captures itself, such as a recursive
+// local function, holds itself through its closed upvalue until the heap collects it.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Class {
+    pub name: Symbol,
+    pub methods: HashMap<Symbol, Rc<Closure>>,
+}
+
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<Symbol, Value>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+#[derive(Clone)]
+pub enum Value {
+    Number(f64),
+    String(Rc<str>),
+    Bool(bool),
+    Nil,
+    NativeFunction(NativeFunction),
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+// matches the formatting of the tree walking interpreter's values
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
+            Value::NativeFunction(nf) => write!(f, "{}", nf.name),
+            Value::Closure(closure) => write!(f, "function {}()", closure.function.name),
+            Value::BoundMethod(bound) => write!(f, "function {}()", bound.method.function.name),
+            Value::Class(class) => write!(f, "{}", class.borrow().name),
+            Value::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+        }
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+// functions, classes and instances are only equal to themselves
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::NativeFunction(a), Value::NativeFunction(b)) => a.name == b.name,
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // index of the frame's slot zero on the value stack
+    base: usize,
+}
+
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Symbol, Value>,
+    // sorted by stack slot, so closing upvalues only looks at the end of the list
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    heap: Heap,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut globals = HashMap::new();
+        globals.insert(
+            Symbol::intern("clock"),
+            Value::NativeFunction(NativeFunction {
+                name: String::from("clock"),
+                arity: 0,
+                callable: |_| {
+                    Ok(Value::Number(
+                        std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_millis() as f64,
+                    ))
+                },
+            }),
+        );
+
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            heap: Heap::new(),
+        }
+    }
+
+    pub fn collect_garbage(&mut self) {
+        self.heap.collect_vm(
+            self.stack.iter().chain(self.globals.values()),
+            self.frames.iter().map(|frame| &frame.closure),
+        );
+    }
+
+    // Calls and loop iterations are safe points, where every value in use is on the stack, in a
+    // global or in a call frame
+    fn safe_point(&mut self) {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+    }
+
+    // for checking that long running sessions don't keep growing
+    #[cfg(test)]
+    pub fn live_objects(&self) -> usize {
+        self.heap.live_objects()
+    }
+
+    pub fn interpret(&mut self, function: Rc<FunctionProto>) -> Result<(), RuntimeError> {
+        let closure = Rc::new(Closure {
+            function,
+            upvalues: vec![],
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: 0,
+        });
+
+        let result = self
+            .run()
+            .map_err(|error_type| self.locate_error(error_type));
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    // points the error at the instruction being executed and records the frames that were active
+    fn locate_error(&self, error_type: RuntimeErrorType) -> RuntimeError {
+        let position = |frame: &CallFrame| {
+            let chunk = &frame.closure.function.chunk;
+            let offset = frame.ip.saturating_sub(1);
+            (
+                chunk.lines[offset],
+                chunk.columns[offset],
+                chunk.lengths[offset],
+            )
+        };
+        let (line, column, length) = position(self.frame());
+        let call_stack = self
+            .frames
+            .windows(2)
+            .rev()
+            .map(|frames| CallSite {
+                function: frames[1].closure.function.name.clone(),
+                line: position(&frames[0]).0,
+            })
+            .collect();
+        RuntimeError {
+            error_type,
+            line,
+            column,
+            length,
+            call_stack,
+        }
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeErrorType> {
+        loop {
+            let byte = self.read_byte();
+            let op = OpCode::from_byte(byte).ok_or_else(|| RuntimeErrorType::Runtime {
+                message: format!("Unknown opcode {}", byte),
+            })?;
+
+            match op {
+                OpCode::Constant => {
+                    let value = match self.read_constant() {
+                        Constant::Number(n) => Value::Number(n),
+                        Constant::String(s) => Value::String(s),
+                        Constant::Name(_) | Constant::Function(_) => Value::Nil,
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let value = self.stack[self.frame().base + slot].clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    self.stack[base + slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_name();
+                    let value = self
+                        .globals
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| RuntimeErrorType::UndefinedVariable(name.to_string()))?;
+                    self.stack.push(value);
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_name();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_name();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => return Err(RuntimeErrorType::UndefinedVariable(name.to_string())),
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_name();
+                    let instance = match self.peek(0) {
+                        Value::Instance(instance) => instance.clone(),
+                        _ => return Err(runtime_error("Only instances have properties")),
+                    };
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => {
+                            let method =
+                                instance.borrow().class.borrow().methods.get(&name).cloned();
+                            match method {
+                                Some(method) => Value::BoundMethod(Rc::new(BoundMethod {
+                                    receiver: Value::Instance(instance.clone()),
+                                    method,
+                                })),
+                                None => {
+                                    return Err(runtime_error(&format!(
+                                        "Undefined property '{}'",
+                                        name
+                                    )))
+                                }
+                            }
+                        }
+                    };
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_name();
+                    let instance = match self.peek(1) {
+                        Value::Instance(instance) => instance.clone(),
+                        _ => return Err(runtime_error("Only instances have fields")),
+                    };
+                    let value = self.pop();
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_name();
+                    let superclass = match self.pop() {
+                        Value::Class(superclass) => superclass,
+                        // `super` is only declared once Inherit has checked it is a class
+                        _ => unreachable!("super is always bound to a class"),
+                    };
+                    let receiver = self.pop();
+                    let method = superclass.borrow().methods.get(&name).cloned();
+                    match method {
+                        Some(method) => self.stack.push(Value::BoundMethod(Rc::new(BoundMethod {
+                            receiver,
+                            method,
+                        }))),
+                        None => {
+                            return Err(runtime_error(&format!("Undefined property '{}'", name)))
+                        }
+                    }
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.stack.push(Value::Bool(a == b));
+                }
+                OpCode::Greater
+                | OpCode::GreaterEqual
+                | OpCode::Less
+                | OpCode::LessEqual
+                | OpCode::Add
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    let result = binary_op(op, a, b)?;
+                    self.stack.push(result);
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!is_truthy(&value)));
+                }
+                OpCode::Negate => match self.pop() {
+                    Value::Number(n) => self.stack.push(Value::Number(-n)),
+                    value => {
+                        return Err(RuntimeErrorType::OperandMustBeNumber(
+                            UnaryOperator::Minus,
+                            value.to_string(),
+                        ))
+                    }
+                },
+                OpCode::Interpolate => {
+                    let count = self.read_byte() as usize;
+                    let parts = self.stack.split_off(self.stack.len() - count);
+                    let mut result = String::new();
+                    for part in parts {
+                        result.push_str(&part.to_string());
+                    }
+                    self.stack.push(Value::String(Rc::from(result)));
+                }
+                OpCode::Print => {
+                    // matches the output of the tree walking interpreter's print statement
+                    match self.pop() {
+                        Value::NativeFunction(native_function) => {
+                            println!("Function: {}", native_function.name)
+                        }
+                        Value::Closure(closure) => println!("Function: {}", closure.function.name),
+                        Value::BoundMethod(bound) => {
+                            println!("Function: {}", bound.method.function.name)
+                        }
+                        value => println!("{}", value),
+                    }
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !is_truthy(self.peek(0)) {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    self.safe_point();
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    self.safe_point();
+                    let arg_count = self.read_byte() as usize;
+                    let callee = self.peek(arg_count).clone();
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::Closure => {
+                    let function = match self.read_constant() {
+                        Constant::Function(function) => function,
+                        _ => return Err(runtime_error("Expected a function constant")),
+                    };
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        if is_local {
+                            let slot = self.frame().base + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            upvalues.push(self.frame().closure.upvalues[index].clone());
+                        }
+                    }
+                    self.stack
+                        .push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.stack.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_name();
+                    let class = self.heap.vm_class(name);
+                    self.stack.push(Value::Class(class));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::Class(superclass) => superclass.clone(),
+                        _ => return Err(runtime_error("Superclass must be a class")),
+                    };
+                    if let Value::Class(subclass) = self.pop() {
+                        // classes can't change once declared, so inherited methods are copied down
+                        subclass.borrow_mut().methods = superclass.borrow().methods.clone();
+                    }
+                }
+                OpCode::Method => {
+                    let name = self.read_name();
+                    let method = match self.pop() {
+                        Value::Closure(method) => method,
+                        _ => return Err(runtime_error("Expected a method closure")),
+                    };
+                    if let Value::Class(class) = self.peek(0) {
+                        class.borrow_mut().methods.insert(name, method);
+                    }
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), RuntimeErrorType> {
+        match callee {
+            Value::Closure(closure) => self.call_closure(closure, arg_count),
+            Value::NativeFunction(native) => {
+                check_arity(&native.name, native.arity, arg_count)?;
+                let args = self.stack.split_off(self.stack.len() - arg_count);
+                let result = (native.callable)(&args)?;
+                self.pop();
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::BoundMethod(bound) => {
+                let slot = self.stack.len() - arg_count - 1;
+                self.stack[slot] = bound.receiver.clone();
+                self.call_closure(bound.method.clone(), arg_count)
+            }
+            Value::Class(class) => {
+                let slot = self.stack.len() - arg_count - 1;
+                let instance = Value::Instance(self.heap.vm_instance(class.clone()));
+                self.stack[slot] = instance;
+                let initializer = class.borrow().methods.get(&Symbol::INIT).cloned();
+                // a class takes the arguments of its init method, and is named in arity errors
+                let arity = initializer
+                    .as_ref()
+                    .map_or(0, |initializer| initializer.function.arity);
+                check_arity(class.borrow().name, arity, arg_count)?;
+                match initializer {
+                    Some(initializer) => self.call_closure(initializer, arg_count),
+                    None => Ok(()),
+                }
+            }
+            _ => Err(RuntimeErrorType::InvalidFunction),
+        }
+    }
+
+    fn call_closure(
+        &mut self,
+        closure: Rc<Closure>,
+        arg_count: usize,
+    ) -> Result<(), RuntimeErrorType> {
+        check_arity(&closure.function.name, closure.function.arity, arg_count)?;
+        if self.frames.len() == FRAMES_MAX {
+            return Err(runtime_error("Stack overflow"));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        for upvalue in self.open_upvalues.iter().rev() {
+            if let Upvalue::Open(open_slot) = &*upvalue.borrow() {
+                if *open_slot == slot {
+                    return upvalue.clone();
+                }
+                if *open_slot < slot {
+                    break;
+                }
+            }
+        }
+
+        let upvalue = self.heap.upvalue(slot);
+        let position = self
+            .open_upvalues
+            .iter()
+            .position(|u| matches!(&*u.borrow(), Upvalue::Open(s) if *s > slot))
+            .unwrap_or(self.open_upvalues.len());
+        self.open_upvalues.insert(position, upvalue.clone());
+        upvalue
+    }
+
+    // moves the values of all open upvalues at or above the given stack slot off the stack
+    fn close_upvalues(&mut self, from_slot: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let slot = match &*upvalue.borrow() {
+                Upvalue::Open(slot) => *slot,
+                Upvalue::Closed(_) => break,
+            };
+            if slot < from_slot {
+                break;
+            }
+            let value = self.stack[slot].clone();
+            *upvalue.borrow_mut() = Upvalue::Closed(value);
+            self.open_upvalues.pop();
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn chunk(&self) -> &Chunk {
+        &self.frame().closure.function.chunk
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let value = self.chunk().read_u16(self.frame().ip);
+        self.frame_mut().ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Constant {
+        let index = self.read_u16() as usize;
+        self.chunk().constants[index].clone()
+    }
+
+    fn read_name(&mut self) -> Symbol {
+        let index = self.read_u16() as usize;
+        match &self.chunk().constants[index] {
+            Constant::Name(name) => *name,
+            constant => Symbol::intern(&constant.to_string()),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+}
+
+fn runtime_error(message: &str) -> RuntimeErrorType {
+    RuntimeErrorType::Runtime {
+        message: String::from(message),
+    }
+}
+
+// nil and false are falsey, everything else is truthy
+fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Bool(false))
+}
+
+fn binary_op(op: OpCode, a: Value, b: Value) -> Result<Value, RuntimeErrorType> {
+    match (op, a, b) {
+        (OpCode::Add, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+        // when one side of + is a string, numbers are converted using their printed form
+        (OpCode::Add, Value::String(a), Value::String(b)) => {
+            Ok(Value::String(Rc::from(format!("{}{}", a, b))))
+        }
+        (OpCode::Add, Value::String(a), Value::Number(b)) => {
+            Ok(Value::String(Rc::from(format!("{}{}", a, b))))
+        }
+        (OpCode::Add, Value::Number(a), Value::String(b)) => {
+            Ok(Value::String(Rc::from(format!("{}{}", a, b))))
+        }
+        (OpCode::Subtract, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+        (OpCode::Multiply, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+        (OpCode::Divide, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a / b)),
+        (OpCode::Greater, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a > b)),
+        (OpCode::GreaterEqual, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a >= b)),
+        (OpCode::Less, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a < b)),
+        (OpCode::LessEqual, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a <= b)),
+        (OpCode::Greater, Value::String(a), Value::String(b)) => Ok(Value::Bool(a > b)),
+        (OpCode::GreaterEqual, Value::String(a), Value::String(b)) => Ok(Value::Bool(a >= b)),
+        (OpCode::Less, Value::String(a), Value::String(b)) => Ok(Value::Bool(a < b)),
+        (OpCode::LessEqual, Value::String(a), Value::String(b)) => Ok(Value::Bool(a <= b)),
+        (op, a, b) => {
+            let symbol = match op {
+                OpCode::Add => "+",
+                OpCode::Subtract => "-",
+                OpCode::Multiply => "*",
+                OpCode::Divide => "/",
+                OpCode::Greater => ">",
+                OpCode::GreaterEqual => ">=",
+                OpCode::Less => "<",
+                _ => "<=",
+            };
+            Err(RuntimeErrorType::Runtime {
+                message: format!("Invalid operation: {} {} {}", a, symbol, b),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::parser::parse;
+    use crate::resolver::resolve;
+    use crate::scanner;
+
+    fn run(vm: &mut Vm, input: &str) -> Result<(), RuntimeError> {
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let function = compile(&statements).unwrap();
+        vm.interpret(function)
+    }
+
+    #[test]
+    fn test_arithmetic_and_globals() {
+        let mut vm = Vm::new();
+        let result = run(&mut vm, "var a = (1 + 2) * 4 - 6 / 3; var b = -a;");
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("a")),
+            Some(&Value::Number(10.0))
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("b")),
+            Some(&Value::Number(-10.0))
+        );
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_locals_loops_and_logic() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            var total = 0;
+            for (var i = 0; i < 5; i = i + 1) {
+                var j = i;
+                if (j == 2 or j == 4) total = total + j;
+            }
+            var guarded = nil and nil.field;
+            ",
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("total")),
+            Some(&Value::Number(6.0))
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("guarded")),
+            Some(&Value::Nil)
+        );
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_chained_assignment() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            var a;
+            var b;
+            a = b = 1;
+            { var c; var d; c = d = 2; a = a + c + d; }
+            ",
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("a")),
+            Some(&Value::Number(5.0))
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("b")),
+            Some(&Value::Number(1.0))
+        );
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_closures_share_captured_variables() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            counter();
+            var second = counter();
+            var other = makeCounter()();
+            ",
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("second")),
+            Some(&Value::Number(2.0))
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("other")),
+            Some(&Value::Number(1.0))
+        );
+    }
+
+    #[test]
+    fn test_recursion() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            var result = fib(15);
+            ",
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("result")),
+            Some(&Value::Number(610.0))
+        );
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            fun f(n) { if (n == 0) return 0; return f(n - 1) + 1; }
+            var deep = f(1000);
+            f(1024);
+            ",
+        );
+
+        assert_eq!(
+            result.map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Stack overflow")
+            })
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("deep")),
+            Some(&Value::Number(1000.0))
+        );
+    }
+
+    #[test]
+    fn test_classes_and_inheritance() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            class Shape {
+                init(size) {
+                    this.size = size;
+                }
+                area() {
+                    return this.size;
+                }
+            }
+            class Square < Shape {
+                area() {
+                    return super.area() * this.size;
+                }
+            }
+            var square = Square(3);
+            var area = square.area();
+            var method = square.area;
+            var bound = method();
+            var label = \"area: ${area}\";
+            ",
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("area")),
+            Some(&Value::Number(9.0))
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("bound")),
+            Some(&Value::Number(9.0))
+        );
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("label")),
+            Some(&Value::String(Rc::from("area: 9")))
+        );
+    }
+
+    #[test]
+    fn test_runtime_errors_match_interpreter() {
+        let mut vm = Vm::new();
+        assert_eq!(
+            run(&mut vm, "\"a\" - 1;").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::Runtime {
+                message: String::from("Invalid operation: a - 1")
+            })
+        );
+        assert_eq!(
+            run(&mut vm, "-nil;").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::OperandMustBeNumber(
+                UnaryOperator::Minus,
+                String::from("nil")
+            ))
+        );
+        assert_eq!(
+            run(&mut vm, "undefined = 1;").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::UndefinedVariable(String::from(
+                "undefined"
+            )))
+        );
+        let error = run(&mut vm, "var a = 1;\nprint a + b;").unwrap_err();
+        assert_eq!(
+            error.error_type,
+            RuntimeErrorType::UndefinedVariable(String::from("b"))
+        );
+        assert_eq!((error.line, error.column), (2, 11));
+        assert_eq!(
+            run(&mut vm, "1();").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::InvalidFunction)
+        );
+        assert_eq!(
+            run(&mut vm, "fun add(a, b) { return a + b; } add(1);").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::ArityMismatch {
+                function: String::from("add"),
+                expected: 2,
+                got: 1
+            })
+        );
+        assert_eq!(
+            run(&mut vm, "clock(1);").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::ArityMismatch {
+                function: String::from("clock"),
+                expected: 0,
+                got: 1
+            })
+        );
+        assert_eq!(
+            run(&mut vm, "class A {} A(1);").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::ArityMismatch {
+                function: String::from("A"),
+                expected: 0,
+                got: 1
+            })
+        );
+        assert_eq!(
+            run(&mut vm, "class B { init(a) {} } B();").map_err(|err| err.error_type),
+            Err(RuntimeErrorType::ArityMismatch {
+                function: String::from("B"),
+                expected: 1,
+                got: 0
+            })
+        );
+        // the vm can be reused after an error
+        assert_eq!(run(&mut vm, "var ok = true;"), Ok(()));
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("ok")),
+            Some(&Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_runtime_error_location_matches_interpreter() {
+        let input = "
+        class Counter {
+            init(start) {
+                this.count = start;
+            }
+            bump() {
+                return this.count + nil;
+            }
+        }
+        fun run() {
+            var counter = Counter(1);
+            return counter.bump();
+        }
+        run();
+        ";
+        let mut vm = Vm::new();
+        let vm_error = run(&mut vm, input).unwrap_err();
+
+        let tokens = scanner::scan(String::from(input)).unwrap();
+        let statements = parse(tokens).unwrap();
+        resolve(&statements).unwrap();
+        let interpreter_error = crate::interpreter::Interpreter::new()
+            .evaluate(&statements)
+            .unwrap_err();
+
+        assert_eq!(vm_error, interpreter_error);
+        assert_eq!(vm_error.line, 7);
+        assert_eq!(vm_error.call_stack.len(), 2);
+    }
+
+    #[test]
+    fn test_unreachable_cycles_are_collected() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            fun outer() { fun inner() { return inner; } return inner; }
+            var f = outer();
+            class Node { init() { this.self = this; } }
+            var node = Node();
+            {
+                class Local { method() { return Local; } }
+                var local = Local;
+            }
+            ",
+        );
+        assert_eq!(result, Ok(()));
+        let inner = match vm.globals.get(&Symbol::intern("f")) {
+            Some(Value::Closure(closure)) => Rc::downgrade(closure),
+            _ => panic!("Expected closure"),
+        };
+        let node = match vm.globals.get(&Symbol::intern("node")) {
+            Some(Value::Instance(instance)) => Rc::downgrade(instance),
+            _ => panic!("Expected instance"),
+        };
+
+        // the block's class and the upvalue its method captured it through are unreachable, but
+        // inner's upvalue, the instance and the global class are not
+        vm.collect_garbage();
+        assert!(inner.upgrade().is_some());
+        assert!(node.upgrade().is_some());
+        assert_eq!(vm.live_objects(), 3);
+
+        assert_eq!(run(&mut vm, "f = nil; node = nil;"), Ok(()));
+        vm.collect_garbage();
+        assert!(inner.upgrade().is_none());
+        assert!(node.upgrade().is_none());
+        assert_eq!(vm.live_objects(), 1);
+    }
+
+    #[test]
+    fn test_long_sessions_collect_automatically() {
+        let mut vm = Vm::new();
+        let result = run(
+            &mut vm,
+            "
+            fun loop() { fun again() { return again; } }
+            fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            class Box { init(value) { this.value = value; } }
+            var counter = makeCounter();
+            var kept = Box(0);
+            for (var i = 0; i < 10000; i = i + 1) {
+                loop();
+                kept = Box(kept.value + counter());
+            }
+            var total = kept.value;
+            ",
+        );
+        assert_eq!(result, Ok(()));
+        assert!(vm.live_objects() <= 2 * crate::gc::INITIAL_THRESHOLD);
+        // collections along the way left everything still in use intact
+        assert_eq!(
+            vm.globals.get(&Symbol::intern("total")),
+            Some(&Value::Number(50005000.0))
+        );
+    }
+}
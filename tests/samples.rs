@@ -0,0 +1,36 @@
+use std::fs;
+use std::process::Command;
+
+fn run_sample(path: &str, use_vm: bool) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cj-compiler"));
+    if use_vm {
+        command.arg("--vm");
+    }
+    let output = command.arg(path).output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_vm_output_matches_interpreter() {
+    let mut paths: Vec<String> = fs::read_dir("samples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path().to_string_lossy().into_owned())
+        .filter(|path| path.ends_with(".cjj"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let interpreted = run_sample(&path, false);
+        let compiled = run_sample(&path, true);
+
+        // clock() returns a different number each run, so only the shape of the output is compared
+        if path.ends_with("functions.cjj") {
+            let is_number = |output: &str| output.lines().next().unwrap().parse::<f64>().is_ok();
+            assert!(is_number(&interpreted), "{}", path);
+            assert!(is_number(&compiled), "{}", path);
+            continue;
+        }
+        assert_eq!(interpreted, compiled, "{}", path);
+    }
+}